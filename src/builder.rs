@@ -0,0 +1,129 @@
+use bytes::Bytes;
+use melstructs::{Address, CoinData, CoinDataHeight, CoinID, CoinValue, Denom, TxKind};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use thiserror::Error;
+
+use crate::PrepareTxArgs;
+
+/// Extra constructors for [CoinData].
+pub trait CoinDataExt {
+    /// Builds an output sending `value` of `denom` to the given covenant, with the given additional data. The covenant hash is computed from the covenant itself, so it can't be gotten wrong.
+    fn to_covenant(
+        cov: &melvm::Covenant,
+        denom: Denom,
+        value: CoinValue,
+        data: impl Into<Bytes>,
+    ) -> CoinData;
+}
+
+impl CoinDataExt for CoinData {
+    fn to_covenant(
+        cov: &melvm::Covenant,
+        denom: Denom,
+        value: CoinValue,
+        data: impl Into<Bytes>,
+    ) -> CoinData {
+        CoinData {
+            covhash: cov.hash(),
+            value,
+            denom,
+            additional_data: data.into(),
+        }
+    }
+}
+
+/// A fluent builder for [PrepareTxArgs], created by [PrepareTxArgs::builder]. Fields that aren't set keep their [Default] values.
+#[derive(Clone, Debug, Default)]
+pub struct PrepareTxArgsBuilder {
+    args: PrepareTxArgs,
+}
+
+impl PrepareTxArgs {
+    /// Starts building a [PrepareTxArgs].
+    pub fn builder() -> PrepareTxArgsBuilder {
+        PrepareTxArgsBuilder::default()
+    }
+}
+
+impl PrepareTxArgsBuilder {
+    /// Adds an output sending `value` of `denom` to `address`, with empty additional data.
+    pub fn output(mut self, address: Address, denom: Denom, value: CoinValue) -> Self {
+        self.args.outputs.push(CoinData {
+            covhash: address,
+            value,
+            denom,
+            additional_data: Bytes::new(),
+        });
+        self
+    }
+
+    /// Sets the kind of the transaction.
+    pub fn kind(mut self, kind: TxKind) -> Self {
+        self.args.kind = kind;
+        self
+    }
+
+    /// Sets the data field of the transaction.
+    pub fn data(mut self, data: impl Into<Bytes>) -> Self {
+        self.args.data = data.into();
+        self
+    }
+
+    /// Adds an out-of-wallet input. See [PrepareTxArgs::inputs].
+    pub fn extra_input(mut self, coin_id: CoinID, coin: CoinDataHeight) -> Self {
+        self.args.inputs.push((coin_id, coin));
+        self
+    }
+
+    /// Adds a covenant, such as one needed to spend an out-of-wallet input. See [PrepareTxArgs::covenants].
+    pub fn extra_covenant(mut self, covenant: impl Into<Bytes>) -> Self {
+        self.args.covenants.push(covenant.into());
+        self
+    }
+
+    /// Finishes building.
+    pub fn build(self) -> PrepareTxArgs {
+        self.args
+    }
+}
+
+/// A reusable template for transactions that pay the same recipients over and over, such as payroll, where only the amounts change from one transaction to the next. Templates can be stored as JSON.
+#[serde_as]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxTemplate {
+    /// "Kind" of the transactions.
+    pub kind: TxKind,
+    /// The "data" field of the transactions. Hex-encoded in JSON.
+    #[serde_as(as = "stdcode::HexBytes")]
+    pub data: Bytes,
+    /// The address and denomination of each recipient, in the order their amounts are given to [TxTemplate::instantiate].
+    pub recipients: Vec<(Address, Denom)>,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("template has {recipients} recipients, but {amounts} amounts were given")]
+    WrongAmountCount { recipients: usize, amounts: usize },
+}
+
+impl TxTemplate {
+    /// Fills in the template with one amount per recipient, giving the arguments for preparing the transaction. Everything else in the arguments keeps its [Default] value.
+    ///
+    /// Fails if the number of amounts doesn't match the number of recipients, which may well happen with templates loaded from storage.
+    pub fn instantiate(&self, amounts: &[CoinValue]) -> Result<PrepareTxArgs, TemplateError> {
+        if amounts.len() != self.recipients.len() {
+            return Err(TemplateError::WrongAmountCount {
+                recipients: self.recipients.len(),
+                amounts: amounts.len(),
+            });
+        }
+        let mut builder = PrepareTxArgs::builder()
+            .kind(self.kind)
+            .data(self.data.clone());
+        for ((address, denom), value) in self.recipients.iter().zip(amounts) {
+            builder = builder.output(*address, *denom, *value);
+        }
+        Ok(builder.build())
+    }
+}
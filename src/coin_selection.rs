@@ -0,0 +1,100 @@
+use melstructs::{CoinDataHeight, CoinID, CoinValue};
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// The order in which [crate::Wallet::prepare_tx] considers the wallet's own coins when picking inputs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CoinSelectionStrategy {
+    /// Picks coins in [CoinID] order. This is effectively arbitrary, but deterministic.
+    #[default]
+    ByCoinId,
+    /// Picks the smallest coins first, which tends to clean up dust at the cost of more inputs.
+    SmallestFirst,
+    /// Picks the largest coins first, minimizing the number of inputs.
+    LargestFirst,
+    /// Picks the coins confirmed at the lowest height first, consolidating old coins.
+    Oldest,
+    /// Picks coins in a random order, so that the selection leaks less about the wallet.
+    Random,
+    /// Like [CoinSelectionStrategy::Random], but shuffles with a PRNG seeded from the given value, so that the same seed and the same coins always give the same selection. This is meant for tests and for reproducing a selection while debugging; reusing a seed in production defeats the purpose of randomizing.
+    SeededRandom(u64),
+    /// Searches for a set of coins that exactly covers what's needed, so that no change output is created. Small MEL excesses are paid as fees instead. Falls back to picking the largest coins first if no exact match is found.
+    BranchAndBound,
+    /// Keeps the transaction tidy: only the tokens the outputs (and the MEL fee) require are touched, and each in a way that adds as few change outputs as possible. Prefers a set of coins that covers what's needed exactly, leaving no change (or MEL change below the dust threshold, which goes to the fee), then the smallest single coin that is enough on its own, leaving a single change output, and falls back to picking the largest coins first. So a token transfer doesn't fragment the MEL holdings beyond paying the fee.
+    Tidy,
+}
+
+impl CoinSelectionStrategy {
+    /// Orders the candidate coins so that the ones to be picked first come first.
+    pub(crate) fn order(&self, coins: &mut [(CoinID, CoinDataHeight)]) {
+        match self {
+            CoinSelectionStrategy::ByCoinId => coins.sort_unstable_by_key(|(id, _)| *id),
+            CoinSelectionStrategy::SmallestFirst => {
+                coins.sort_by_key(|(_, cdh)| cdh.coin_data.value)
+            }
+            CoinSelectionStrategy::LargestFirst
+            | CoinSelectionStrategy::BranchAndBound
+            | CoinSelectionStrategy::Tidy => {
+                coins.sort_by_key(|(_, cdh)| std::cmp::Reverse(cdh.coin_data.value))
+            }
+            CoinSelectionStrategy::Oldest => coins.sort_by_key(|(_, cdh)| cdh.height),
+            CoinSelectionStrategy::Random => coins.shuffle(&mut rand::thread_rng()),
+            CoinSelectionStrategy::SeededRandom(seed) => {
+                // start from a canonical order, so that the result depends only on the seed and the coins
+                coins.sort_unstable_by_key(|(id, _)| *id);
+                coins.shuffle(&mut ChaCha8Rng::seed_from_u64(*seed))
+            }
+        }
+    }
+}
+
+/// How many search nodes [select_exact] visits before giving up.
+const BNB_MAX_VISITS: usize = 100_000;
+
+/// Branch-and-bound search for a subset of `values` whose sum lies within `[target, target + tolerance]`. Returns the indices of the chosen values, or `None` if no such subset was found within [BNB_MAX_VISITS] search nodes.
+pub(crate) fn select_exact(
+    values: &[CoinValue],
+    target: CoinValue,
+    tolerance: CoinValue,
+) -> Option<Vec<usize>> {
+    // search the largest values first, so that we overshoot (and prune) early
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(values[i]));
+    let sorted: Vec<u128> = order.iter().map(|&i| values[i].0).collect();
+    // remaining[i] is the sum of everything from i onwards
+    let mut remaining = vec![0u128; sorted.len() + 1];
+    for i in (0..sorted.len()).rev() {
+        remaining[i] = remaining[i + 1].saturating_add(sorted[i]);
+    }
+
+    let upper = target.0.saturating_add(tolerance.0);
+    // depth-first search with an explicit stack, since there may be far too many coins to recurse over. Each entry is a search node: the next value to decide on, the sum so far, and how many of the chosen values lead to it, plus the value it chose last, if any.
+    let mut chosen: Vec<usize> = vec![];
+    let mut stack: Vec<(usize, u128, usize, Option<usize>)> = vec![(0, 0, 0, None)];
+    let mut visits = 0;
+    while let Some((idx, sum, depth, last)) = stack.pop() {
+        chosen.truncate(depth);
+        chosen.extend(last);
+        visits += 1;
+        if visits > BNB_MAX_VISITS {
+            return None;
+        }
+        if sum >= target.0 {
+            if sum <= upper {
+                return Some(chosen.into_iter().map(|i| order[i]).collect());
+            }
+            continue;
+        }
+        if idx == sorted.len() || sum.saturating_add(remaining[idx]) < target.0 {
+            continue;
+        }
+        // the stack is last in, first out, so this searches including the value before skipping it
+        stack.push((idx + 1, sum, chosen.len(), None));
+        let with = sum.saturating_add(sorted[idx]);
+        if with <= upper {
+            stack.push((idx + 1, with, chosen.len(), Some(idx)));
+        }
+    }
+    None
+}
@@ -0,0 +1,79 @@
+use std::fmt::Display;
+
+use melstructs::{CoinValue, Denom};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::Wallet;
+
+/// An amount of a particular token. Unlike a bare [CoinValue], it can't be added to or subtracted from an amount of a different token by mistake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DenomValue {
+    /// The token.
+    pub denom: Denom,
+    /// How much of it.
+    pub value: CoinValue,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DenomValueError {
+    #[error("cannot combine amounts of {left} and {right}")]
+    Mismatch { left: Denom, right: Denom },
+
+    #[error("amount of {0} out of range")]
+    OutOfRange(Denom),
+}
+
+impl DenomValue {
+    /// Creates an amount of the given token.
+    pub fn new(denom: Denom, value: CoinValue) -> Self {
+        Self { denom, value }
+    }
+
+    /// Adds another amount of the same token. Fails if the tokens differ, or if the sum overflows.
+    pub fn checked_add(self, other: DenomValue) -> Result<DenomValue, DenomValueError> {
+        let value = self
+            .same_denom(&other)?
+            .value
+            .checked_add(other.value)
+            .ok_or(DenomValueError::OutOfRange(self.denom))?;
+        Ok(Self::new(self.denom, value))
+    }
+
+    /// Subtracts another amount of the same token. Fails if the tokens differ, or if the other amount is larger.
+    pub fn checked_sub(self, other: DenomValue) -> Result<DenomValue, DenomValueError> {
+        let value = self
+            .same_denom(&other)?
+            .value
+            .checked_sub(other.value)
+            .ok_or(DenomValueError::OutOfRange(self.denom))?;
+        Ok(Self::new(self.denom, value))
+    }
+
+    fn same_denom(self, other: &DenomValue) -> Result<Self, DenomValueError> {
+        if self.denom == other.denom {
+            Ok(self)
+        } else {
+            Err(DenomValueError::Mismatch {
+                left: self.denom,
+                right: other.denom,
+            })
+        }
+    }
+}
+
+impl Display for DenomValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.value, self.denom)
+    }
+}
+
+impl Wallet {
+    /// Lists the balances of the wallet like [Wallet::balances], but as [DenomValue]s, for callers doing arithmetic on them.
+    pub fn typed_balances(&self) -> Vec<DenomValue> {
+        self.balances()
+            .into_iter()
+            .map(|(denom, value)| DenomValue::new(denom, value))
+            .collect()
+    }
+}
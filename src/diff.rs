@@ -0,0 +1,59 @@
+use melstructs::{CoinDataHeight, CoinID, Transaction, TxHash};
+use serde::{Deserialize, Serialize};
+
+use crate::Wallet;
+
+/// What changed between two states of a [Wallet], as returned by [Wallet::diff].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletDiff {
+    /// Confirmed coins that only the newer state has.
+    pub added_utxos: Vec<(CoinID, CoinDataHeight)>,
+    /// Confirmed coins that only the older state has.
+    pub removed_utxos: Vec<CoinID>,
+    /// Pending transactions that only the newer state has.
+    pub added_pending: Vec<Transaction>,
+    /// Pending transactions that only the older state has.
+    pub removed_pending: Vec<TxHash>,
+}
+
+impl WalletDiff {
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.added_utxos.is_empty()
+            && self.removed_utxos.is_empty()
+            && self.added_pending.is_empty()
+            && self.removed_pending.is_empty()
+    }
+}
+
+impl Wallet {
+    /// Works out what changed from this state of the wallet to `other`, a newer state, in terms of confirmed coins and pending transactions. This lets UIs that keep snapshots of a wallet update just what changed.
+    pub fn diff(&self, other: &Wallet) -> WalletDiff {
+        WalletDiff {
+            added_utxos: other
+                .confirmed_utxos
+                .iter()
+                .filter(|(k, _)| !self.confirmed_utxos.contains_key(k))
+                .map(|(k, v)| (*k, v.clone()))
+                .collect(),
+            removed_utxos: self
+                .confirmed_utxos
+                .keys()
+                .filter(|k| !other.confirmed_utxos.contains_key(k))
+                .copied()
+                .collect(),
+            added_pending: other
+                .pending_outgoing
+                .iter()
+                .filter(|(k, _)| !self.pending_outgoing.contains_key(k))
+                .map(|(_, tx)| tx.clone())
+                .collect(),
+            removed_pending: self
+                .pending_outgoing
+                .keys()
+                .filter(|k| !other.pending_outgoing.contains_key(k))
+                .copied()
+                .collect(),
+        }
+    }
+}
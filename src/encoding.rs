@@ -0,0 +1,114 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use melstructs::CoinID;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use serde_with::{DeserializeAs, SerializeAs};
+use thiserror::Error;
+
+use crate::Wallet;
+
+/// The version of the binary wallet layout that [Wallet::to_bytes] produces.
+pub const WALLET_VERSION: u32 = 1;
+
+/// A binary-encoded wallet, tagged with the version of its layout, so that wallets persisted by older versions of this crate can still be read by [migrate].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionedWallet {
+    /// The version of the layout of `inner`.
+    pub version: u32,
+    /// The `stdcode` encoding of the wallet, in the layout given by `version`.
+    pub inner: Vec<u8>,
+}
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("unknown wallet version {0}")]
+    UnknownVersion(u32),
+
+    #[error("malformed wallet encoding: {0}")]
+    Malformed(#[from] bincode::Error),
+
+    #[error("coin {0} does not belong to the wallet address")]
+    WrongAddress(CoinID),
+}
+
+impl Wallet {
+    /// Encodes the wallet in its canonical, compact binary form: a [VersionedWallet] with the current [WALLET_VERSION], using `stdcode`. This is much smaller than JSON for wallets with many coins. Decode it with [Wallet::from_bytes].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        stdcode::serialize(&VersionedWallet {
+            version: WALLET_VERSION,
+            inner: stdcode::serialize(self).unwrap(),
+        })
+        .unwrap()
+    }
+
+    /// Decodes a wallet encoded by [Wallet::to_bytes], by this or an older version of this crate. This is the same as [migrate], so the same checks apply to untrusted blobs.
+    pub fn from_bytes(b: &[u8]) -> Result<Self, MigrationError> {
+        migrate(b)
+    }
+}
+
+/// Decodes a binary-encoded wallet of any known version, transforming older layouts into the current one.
+///
+/// Version 0 is the untagged layout from before wallets were versioned: just the `stdcode` encoding of the wallet. Since nothing marks a blob as untagged, some version 0 blobs also happen to parse as a [VersionedWallet], with garbage inside; blobs that can't be decoded as tagged are therefore decoded as version 0 instead.
+///
+/// Since blobs may come from untrusted sources, the decoded wallet is checked: blobs listing the same coin (or any other key) twice are rejected as [MigrationError::Malformed], rather than silently keeping the last entry, and so are blobs with confirmed coins that don't belong to the wallet's address, as [MigrationError::WrongAddress].
+pub fn migrate(bytes: &[u8]) -> Result<Wallet, MigrationError> {
+    let wallet = match stdcode::deserialize::<VersionedWallet>(bytes) {
+        // if the tagged blob turns out to be garbage, it may well be an untagged one instead, so report the tagged error only if that fails too
+        Ok(versioned) => {
+            decode_versioned(&versioned).or_else(|e| decode_v1(bytes).map_err(|_| e))?
+        }
+        Err(_) => decode_v1(bytes)?,
+    };
+    if let Some((coin_id, _)) = wallet
+        .confirmed_utxos
+        .iter()
+        .find(|(_, cdh)| cdh.coin_data.covhash != wallet.address)
+    {
+        return Err(MigrationError::WrongAddress(*coin_id));
+    }
+    Ok(wallet)
+}
+
+/// Decodes a tagged wallet of any known version.
+fn decode_versioned(versioned: &VersionedWallet) -> Result<Wallet, MigrationError> {
+    match versioned.version {
+        1 => decode_v1(&versioned.inner),
+        other => Err(MigrationError::UnknownVersion(other)),
+    }
+}
+
+/// Decodes the version 1 layout, which version 0 shares without the version tag.
+fn decode_v1(bytes: &[u8]) -> Result<Wallet, MigrationError> {
+    Ok(stdcode::deserialize(bytes)?)
+}
+
+/// Encodes a map as a list of key-value pairs, like `Vec<(Same, Same)>`, but refuses to decode lists that repeat a key.
+pub(crate) struct UniquePairs;
+
+impl<K: Serialize, V: Serialize> SerializeAs<BTreeMap<K, V>> for UniquePairs {
+    fn serialize_as<S: Serializer>(
+        source: &BTreeMap<K, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(source.iter())
+    }
+}
+
+impl<'de, K, V> DeserializeAs<'de, BTreeMap<K, V>> for UniquePairs
+where
+    K: Deserialize<'de> + Ord + Display,
+    V: Deserialize<'de>,
+{
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error> {
+        let pairs = Vec::<(K, V)>::deserialize(deserializer)?;
+        let mut map = BTreeMap::new();
+        for (k, v) in pairs {
+            if map.contains_key(&k) {
+                return Err(D::Error::custom(format!("duplicate key {}", k)));
+            }
+            map.insert(k, v);
+        }
+        Ok(map)
+    }
+}
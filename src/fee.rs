@@ -0,0 +1,72 @@
+use melstructs::{CoinValue, Transaction};
+use serde::{Deserialize, Serialize};
+
+use crate::{PrepareTxArgs, PrepareTxError, Signer, Wallet};
+
+/// How much fee a transaction should pay, relative to the network's current fee multiplier. Use it with [Wallet::prepare_tx_with_policy] instead of working out a raw fee multiplier at every call site.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FeePolicy {
+    /// Pay the least fee the network accepts.
+    Minimum,
+    /// Scale the network's fee multiplier by the given factor, for transactions that can wait. The factor counts as at least 1 and at most [FAST_FEE_FACTOR], so the fee is anywhere from [FeePolicy::Minimum] up to what [FeePolicy::Fast] pays at the least.
+    Economical(f64),
+    /// Scale the network's fee multiplier by the given factor, for transactions that should confirm quickly. The factor counts as at least [FAST_FEE_FACTOR], so `Fast(0.0)` is a sensible default.
+    Fast(f64),
+    /// Pay exactly this fee, as with [PrepareTxArgs::fee_override].
+    Exact(CoinValue),
+}
+
+/// The factor where [FeePolicy::Economical] ends and [FeePolicy::Fast] begins.
+pub const FAST_FEE_FACTOR: f64 = 1.5;
+
+impl FeePolicy {
+    /// The fee multiplier this policy uses, given the network's current fee multiplier. Returns `None` if the factor is negative, infinite or NaN.
+    pub fn fee_multiplier(&self, network_multiplier: u128) -> Option<u128> {
+        if self.bad_factor().is_some() {
+            return None;
+        }
+        let factor = match *self {
+            FeePolicy::Economical(factor) => factor.clamp(1.0, FAST_FEE_FACTOR),
+            FeePolicy::Fast(factor) => factor.max(FAST_FEE_FACTOR),
+            FeePolicy::Minimum | FeePolicy::Exact(_) => return Some(network_multiplier),
+        };
+        // float-to-int casts saturate, so a huge factor can't wrap around to a tiny fee
+        Some(((network_multiplier as f64 * factor) as u128).max(network_multiplier))
+    }
+
+    /// The factor of this policy, if it has one that doesn't make sense.
+    fn bad_factor(&self) -> Option<f64> {
+        match *self {
+            FeePolicy::Economical(factor) | FeePolicy::Fast(factor)
+                if !factor.is_finite() || factor < 0.0 =>
+            {
+                Some(factor)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Wallet {
+    /// Like [Wallet::prepare_tx], but decides the fee with a [FeePolicy] applied to the network's current fee multiplier.
+    ///
+    /// Fails with [PrepareTxError::BadFeeFactor] if the policy's factor is negative, infinite or NaN.
+    pub fn prepare_tx_with_policy<S: Signer>(
+        &self,
+        mut args: PrepareTxArgs,
+        signer: &S,
+        network_multiplier: u128,
+        policy: FeePolicy,
+    ) -> Result<Transaction, PrepareTxError<S::Error>> {
+        if let Some(factor) = policy.bad_factor() {
+            return Err(PrepareTxError::BadFeeFactor(factor));
+        }
+        if let FeePolicy::Exact(fee) = policy {
+            args.fee_override = Some(fee);
+        }
+        let fee_multiplier = policy
+            .fee_multiplier(network_multiplier)
+            .expect("factor already checked");
+        self.prepare_tx(args, signer, fee_multiplier)
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+
+use melstructs::{BlockHeight, CoinDataHeight, CoinID, Denom, Transaction, TxHash, TxKind};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, Same};
+
+use crate::{signed_value, Wallet};
+
+/// One transaction that changed what a [Wallet] holds, as recorded in [Wallet::history].
+#[serde_as]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// The hash of the transaction. Coins spent by transactions the wallet didn't know about are attributed to an all-zeroes hash.
+    pub txhash: TxHash,
+    /// The height of the block that confirmed the transaction.
+    pub height: BlockHeight,
+    #[serde_as(as = "Vec<(Same, Same)>")]
+    /// How much the transaction changed the wallet's balance, by token: positive for coins received, negative for coins spent.
+    pub net_change: BTreeMap<Denom, i128>,
+    /// The kind of the transaction. This is only known for transactions the wallet sent itself; incoming transactions are recorded as [TxKind::Normal].
+    pub kind: TxKind,
+}
+
+impl HistoryEntry {
+    /// Whether this entry is not a transaction but the reward of the block proposer, as listed by [Wallet::coinbase_coins]. Such entries have the hash of the pseudo-[CoinID] of the reward as their `txhash`.
+    pub fn is_proposer_reward(&self) -> bool {
+        self.txhash == CoinID::proposer_reward(self.height).txhash
+    }
+}
+
+impl Wallet {
+    /// Lists every transaction that changed the coins of this wallet, oldest first. Entries are added by [Wallet::add_coins] and removed by [Wallet::rollback_to] when their blocks are undone.
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
+    /// Iterates over the confirmed coins that are block proposer rewards, rather than outputs of transactions. The network gives each reward coin the pseudo-[CoinID] from [CoinID::proposer_reward] for the height of its block.
+    pub fn coinbase_coins(&self) -> impl Iterator<Item = (&CoinID, &CoinDataHeight)> {
+        self.confirmed_utxos
+            .iter()
+            .filter(|(coin_id, cdh)| **coin_id == CoinID::proposer_reward(cdh.height))
+    }
+
+    /// Works out how the given transaction would change the balances of this wallet, by token, in the same terms as [HistoryEntry::net_change]: the inputs that are confirmed coins of this wallet count as spent, and the outputs to this wallet's address count as received. This is useful for confirmation dialogs before broadcasting.
+    pub fn net_change_of(&self, tx: &Transaction) -> BTreeMap<Denom, i128> {
+        let mut net_change: BTreeMap<Denom, i128> = BTreeMap::new();
+        for cdh in tx
+            .inputs
+            .iter()
+            .filter_map(|coin| self.confirmed_utxos.get(coin))
+        {
+            let change = net_change.entry(cdh.coin_data.denom).or_default();
+            *change = change.saturating_sub(signed_value(cdh.coin_data.value));
+        }
+        for output in tx.outputs.iter().filter(|o| o.covhash == self.address) {
+            let change = net_change.entry(output.denom).or_default();
+            *change = change.saturating_add(signed_value(output.value));
+        }
+        net_change.retain(|_, change| *change != 0);
+        net_change
+    }
+}
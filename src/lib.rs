@@ -1,10 +1,45 @@
+mod builder;
+mod coin_selection;
+mod denom_value;
+mod diff;
+mod encoding;
+mod fee;
+mod history;
+mod merge;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod partial;
+mod reserve;
+mod rollback;
 mod signer;
+mod stake;
+mod stats;
+mod sweep;
+#[cfg(feature = "testing")]
+pub mod test_util;
+mod threshold;
+mod verify;
+pub use builder::*;
 use bytes::Bytes;
+pub use coin_selection::*;
+pub use denom_value::*;
+pub use diff::*;
+pub use encoding::*;
+pub use fee::*;
+pub use history::*;
+pub use merge::*;
+pub use partial::*;
+pub use reserve::*;
+pub use rollback::*;
 use serde_with::{serde_as, Same};
 pub use signer::*;
+pub use stats::*;
+pub use threshold::*;
+pub use verify::*;
 
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    convert::Infallible,
     error::Error,
 };
 
@@ -25,12 +60,117 @@ pub struct Wallet {
     pub address: Address,
     /// The latest block height known to this wallet.
     pub height: BlockHeight,
-    #[serde_as(as = "Vec<(Same, Same)>")]
+    #[serde_as(as = "UniquePairs")]
     /// All the *confirmed* UTXOs: output coins of confirmed transactions that this wallet can spend.
     pub confirmed_utxos: BTreeMap<CoinID, CoinDataHeight>,
-    #[serde_as(as = "Vec<(Same, Same)>")]
+    #[serde_as(as = "UniquePairs")]
     /// Pending outgoing transactions. These transactions' outputs may be further spent in more transactions, but they aren't confirmed yet. We use a map in order to ensure deduplication.
     pub pending_outgoing: BTreeMap<TxHash, Transaction>,
+    #[serde_as(as = "UniquePairs")]
+    /// The wallet height at which each pending transaction was added, so that [Wallet::evict_expired_pending] can drop the ones that never confirm. Optional in JSON, defaulting to an empty list.
+    #[serde(default)]
+    pub pending_heights: BTreeMap<TxHash, BlockHeight>,
+    /// Undo information for the most recent blocks, oldest first, so that [Wallet::rollback_to] can handle reorgs. Holds at most [MAX_ROLLBACK_DEPTH] entries. Optional in JSON, defaulting to an empty list.
+    #[serde(default)]
+    pub recent_blocks: VecDeque<BlockUndo>,
+    /// Every transaction that changed the coins of this wallet, oldest first. See [Wallet::history]. Optional in JSON, defaulting to an empty list.
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+    #[serde_as(as = "UniquePairs")]
+    /// The height from which each coin with a time-locked covenant can be spent. Coins not listed here are spendable right away. Optional in JSON, defaulting to an empty list.
+    #[serde(default)]
+    pub maturity: BTreeMap<CoinID, BlockHeight>,
+    /// Coins that the user has set aside with [Wallet::freeze], which [Wallet::prepare_tx] won't pick. Optional in JSON, defaulting to an empty list.
+    #[serde(default)]
+    pub frozen: BTreeSet<CoinID>,
+    /// If set, the only denominations whose coins the wallet keeps track of. Coins of other denominations sent to this wallet are ignored, so that lightweight clients don't need to store them. Optional in JSON, defaulting to tracking every denomination.
+    #[serde(default)]
+    pub tracked_denoms: Option<BTreeSet<Denom>>,
+    /// Coins set aside with [Wallet::reserve], which [Wallet::prepare_tx] won't pick. Never serialized, and not compared by `==` either, so that a wallet still equals itself after a round trip through [Wallet::to_bytes].
+    #[serde(skip)]
+    pub reservations: Reservations,
+}
+
+impl PartialEq for Wallet {
+    fn eq(&self, other: &Self) -> bool {
+        // destructure, so that new fields can't be forgotten here
+        let Wallet {
+            netid,
+            address,
+            height,
+            confirmed_utxos,
+            pending_outgoing,
+            pending_heights,
+            recent_blocks,
+            history,
+            maturity,
+            frozen,
+            tracked_denoms,
+            reservations: _,
+        } = self;
+        *netid == other.netid
+            && *address == other.address
+            && *height == other.height
+            && *confirmed_utxos == other.confirmed_utxos
+            && *pending_outgoing == other.pending_outgoing
+            && *pending_heights == other.pending_heights
+            && *recent_blocks == other.recent_blocks
+            && *history == other.history
+            && *maturity == other.maturity
+            && *frozen == other.frozen
+            && *tracked_denoms == other.tracked_denoms
+    }
+}
+
+impl Eq for Wallet {}
+
+/// How far a [Wallet] has synced, as returned by [Wallet::sync_status].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncStatus {
+    /// The latest block height known to the wallet.
+    pub local_height: BlockHeight,
+    /// How many confirmed coins the wallet holds.
+    pub utxo_count: usize,
+    /// How many transactions are pending.
+    pub pending_count: usize,
+}
+
+/// The status of a confirmed coin of a [Wallet], as reported by [Wallet::all_coins].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CoinStatus {
+    /// The coin is confirmed and not held back.
+    Confirmed,
+    /// The coin is being spent by the pending transaction with this hash.
+    LockedByPending(TxHash),
+    /// The coin has been frozen with [Wallet::freeze].
+    Frozen,
+    /// The coin has been reserved with [Wallet::reserve].
+    Reserved(ReservationId),
+}
+
+/// What applying coin diffs with [Wallet::add_coins] changed, so that callers can react, for instance by telling the user that a payment confirmed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddCoinsOutcome {
+    /// The pending transactions that are no longer pending, because the coin diffs show they confirmed (or were beaten by a conflicting spend).
+    pub confirmed_pending: Vec<TxHash>,
+    /// The coins added to the wallet.
+    pub received: Vec<CoinID>,
+    /// The coins removed from the wallet because they were spent.
+    pub spent: Vec<CoinID>,
+}
+
+impl AddCoinsOutcome {
+    fn of(undo: &BlockUndo) -> Self {
+        Self {
+            confirmed_pending: undo
+                .confirmed_pending
+                .iter()
+                .map(|tx| tx.hash_nosigs())
+                .collect(),
+            received: undo.added.clone(),
+            spent: undo.removed.iter().map(|(coin_id, _)| *coin_id).collect(),
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -40,61 +180,423 @@ pub enum AddCoinsError {
 
     #[error("address of added coins does not match the wallet address")]
     WrongAddress,
+
+    #[error("network of added coins does not match the wallet network")]
+    WrongNetwork,
+}
+
+#[derive(Error, Debug)]
+pub enum AddPendingError {
+    #[error("transaction spends coins already spent by pending transaction {0}")]
+    Conflict(TxHash),
+}
+
+#[derive(Error, Debug)]
+pub enum ReplaceError {
+    #[error("transaction {0} is not pending")]
+    NotPending(TxHash),
+
+    #[error("replacement does not spend coin {0} of the replaced transaction")]
+    MissingInput(CoinID),
+
+    #[error("replacement fee of {new} is not higher than the replaced fee of {old}")]
+    FeeNotHigher { old: CoinValue, new: CoinValue },
+
+    #[error("replacement spends coins already spent by pending transaction {0}")]
+    Conflict(TxHash),
 }
 
 impl Wallet {
-    /// Lists the balances of the wallet, by token.
+    /// Creates an empty wallet at height 0, tracking the coins of the given address on the given network.
+    pub fn new(netid: NetID, address: Address) -> Self {
+        Self {
+            netid,
+            address,
+            height: BlockHeight(0),
+            confirmed_utxos: BTreeMap::new(),
+            pending_outgoing: BTreeMap::new(),
+            pending_heights: BTreeMap::new(),
+            recent_blocks: VecDeque::new(),
+            history: vec![],
+            maturity: BTreeMap::new(),
+            frozen: BTreeSet::new(),
+            tracked_denoms: None,
+            reservations: Reservations::default(),
+        }
+    }
+
+    /// Creates an empty wallet at height 0 for the coins that the given signer can spend, so that the address is guaranteed to match the signer's covenant.
+    pub fn from_signer(netid: NetID, signer: &impl Signer) -> Self {
+        Self::new(netid, tmelcrypt::hash_single(signer.covenant()).into())
+    }
+
+    /// Checks whether the covenant of the given signer hashes to the address of this wallet, that is, whether the signer can spend the coins of this wallet.
+    pub fn matches_signer(&self, signer: &impl Signer) -> bool {
+        self.address == tmelcrypt::hash_single(signer.covenant()).into()
+    }
+
+    /// Lists the balances of the wallet, by token. This counts every confirmed coin, including those that pending transactions are already spending; see [Wallet::spendable_balances] for what [Wallet::prepare_tx] can actually use.
+    ///
+    /// Balances, like all sums of coin values in this crate, saturate at `u128::MAX` instead of overflowing. Since the network caps coin values at [melstructs::MAX_COINVAL], this only happens with crafted coin diffs.
     pub fn balances(&self) -> BTreeMap<Denom, CoinValue> {
+        tally(self.confirmed_utxos.values().map(|cdh| &cdh.coin_data))
+    }
+
+    /// Lists the balances of the wallet like [Wallet::balances], but in the order UIs usually show them: MEL first, then the other tokens from the largest balance to the smallest, with ties broken by the natural order of [Denom]. The map from [Wallet::balances] simply follows that natural order.
+    pub fn balances_ordered(&self) -> Vec<(Denom, CoinValue)> {
+        let mut balances: Vec<(Denom, CoinValue)> = self.balances().into_iter().collect();
+        balances.sort_by_key(|(denom, value)| (*denom != Denom::Mel, std::cmp::Reverse(*value)));
+        balances
+    }
+
+    /// Returns the balance of the given token, counting every confirmed coin like [Wallet::balances]. This is zero for tokens the wallet doesn't hold.
+    pub fn balance(&self, denom: Denom) -> CoinValue {
+        tally(self.coins_for_denom(denom).map(|(_, cdh)| &cdh.coin_data))
+            .remove(&denom)
+            .unwrap_or_default()
+    }
+
+    /// Iterates over the confirmed coins of the given denomination, including those locked by pending transactions.
+    pub fn coins_for_denom(
+        &self,
+        denom: Denom,
+    ) -> impl Iterator<Item = (&CoinID, &CoinDataHeight)> + '_ {
+        self.confirmed_utxos
+            .iter()
+            .filter(move |(_, cdh)| cdh.coin_data.denom == denom)
+    }
+
+    /// Returns the confirmed coin of the given denomination with the highest value, if there are any. Ties go to the coin with the lowest [CoinID].
+    pub fn largest_coin(&self, denom: Denom) -> Option<(CoinID, CoinDataHeight)> {
+        self.coins_for_denom(denom)
+            .max_by_key(|(coin, cdh)| (cdh.coin_data.value, std::cmp::Reverse(**coin)))
+            .map(|(coin, cdh)| (*coin, cdh.clone()))
+    }
+
+    /// Lists every token the wallet holds confirmed coins of.
+    pub fn denoms(&self) -> BTreeSet<Denom> {
         self.confirmed_utxos
             .values()
-            .fold(BTreeMap::new(), |mut map, cdh| {
-                map.entry(cdh.coin_data.denom).or_default().0 += cdh.coin_data.value.0;
-                map
-            })
+            .map(|cdh| cdh.coin_data.denom)
+            .collect()
+    }
+
+    /// Lists the balances of the wallet, by token, counting only the coins that [Wallet::prepare_tx] is allowed to spend. This is never more than [Wallet::balances].
+    pub fn spendable_balances(&self) -> BTreeMap<Denom, CoinValue> {
+        tally(self.spendable_utxos().map(|(_, cdh)| &cdh.coin_data))
+    }
+
+    /// Returns how much of the given token can be sent while leaving `reserve_fee` MEL aside for the fee, so that a UI can offer a "send max" amount that won't fail for lack of MEL. The reserve only comes out of the MEL balance, since fees are always paid in MEL; the balance of other tokens is the same as in [Wallet::spendable_balances]. Never goes below zero.
+    pub fn effective_balance(&self, denom: Denom, reserve_fee: CoinValue) -> CoinValue {
+        let spendable = self.spendable_balances().remove(&denom).unwrap_or_default();
+        if denom == FEE_DENOM {
+            spendable.checked_sub(reserve_fee).unwrap_or_default()
+        } else {
+            spendable
+        }
+    }
+
+    /// Lists the balances the wallet will have, by token, once all pending transactions confirm: the confirmed balances, less the coins that pending transactions spend, plus what they send back to this wallet (such as change).
+    pub fn pending_balances(&self) -> BTreeMap<Denom, CoinValue> {
+        let pending_inputs = self.pending_inputs();
+        tally(
+            self.confirmed_utxos
+                .iter()
+                .filter(|(k, _)| !pending_inputs.contains(k))
+                .map(|(_, cdh)| &cdh.coin_data)
+                .chain(
+                    self.pending_outgoing
+                        .values()
+                        .flat_map(|tx| tx.outputs.iter())
+                        .filter(|output| output.covhash == self.address),
+                ),
+        )
+    }
+
+    /// Lists the balances of the wallet, by token, excluding the coins that pending transactions are spending.
+    pub fn available_balances(&self) -> BTreeMap<Denom, CoinValue> {
+        let pending_inputs = self.pending_inputs();
+        tally(
+            self.confirmed_utxos
+                .iter()
+                .filter(|(k, _)| !pending_inputs.contains(k))
+                .map(|(_, cdh)| &cdh.coin_data),
+        )
+    }
+
+    /// Returns the height of the next block that [Wallet::add_coins] expects.
+    pub fn next_height(&self) -> BlockHeight {
+        self.height + BlockHeight(1)
+    }
+
+    /// Summarizes how far the wallet has synced, for reporting progress.
+    pub fn sync_status(&self) -> SyncStatus {
+        SyncStatus {
+            local_height: self.height,
+            utxo_count: self.confirmed_utxos.len(),
+            pending_count: self.pending_outgoing.len(),
+        }
     }
 
-    /// Adds all the coin diffs at a particular block height. Clears pending transactions that the coin diffs show are
+    /// Adds all the coin diffs at a particular block height of the given network. Clears pending transactions that the coin diffs show are confirmed (because coins they create arrive, or coins they spend are spent), and records every transaction involved in [Wallet::history]. The height must be exactly one above the current height of the wallet.
+    ///
+    /// Returns which pending transactions were cleared and which coins were received and spent.
     pub fn add_coins(
         &mut self,
+        netid: NetID,
         height: BlockHeight,
         new_coins: impl IntoIterator<Item = (CoinID, CoinData)>,
         spent_coins: impl IntoIterator<Item = CoinID>,
-    ) -> Result<(), AddCoinsError> {
-        if height != self.height + BlockHeight(1) {
+    ) -> Result<AddCoinsOutcome, AddCoinsError> {
+        if height != self.next_height() {
             return Err(AddCoinsError::BadHeight);
         }
+        self.add_coins_at(netid, height, new_coins, spent_coins)
+    }
+
+    /// Like [Wallet::add_coins], but accepts any height above the current height of the wallet, jumping straight to it. This is useful when catching up, but the caller is responsible for making sure that the skipped blocks contain no coin diffs relevant to this wallet.
+    pub fn add_coins_at(
+        &mut self,
+        netid: NetID,
+        height: BlockHeight,
+        new_coins: impl IntoIterator<Item = (CoinID, CoinData)>,
+        spent_coins: impl IntoIterator<Item = CoinID>,
+    ) -> Result<AddCoinsOutcome, AddCoinsError> {
+        if netid != self.netid {
+            return Err(AddCoinsError::WrongNetwork);
+        }
+        if height <= self.height {
+            return Err(AddCoinsError::BadHeight);
+        }
+        // check everything before touching the wallet, so that if things fail we don't leave it in a bad state
+        let new_coins: Vec<(CoinID, CoinData)> = new_coins.into_iter().collect();
+        if new_coins
+            .iter()
+            .any(|(_, coin_data)| coin_data.covhash != self.address)
+        {
+            return Err(AddCoinsError::WrongAddress);
+        }
         let spent_coins = spent_coins.into_iter().collect::<HashSet<_>>();
 
-        // we put everything in a temporary hashmap, so that if things fail we don't leave the wallet in a bad state
-        let mut accum = HashMap::new();
-        for (coin_id, coin_data) in new_coins.into_iter() {
-            if coin_data.covhash != self.address {
+        let mut undo = BlockUndo {
+            prev_height: self.height,
+            height,
+            added: vec![],
+            removed: vec![],
+            confirmed_pending: vec![],
+        };
+        self.apply_block(
+            height,
+            new_coins,
+            &spent_coins,
+            &spent_coins,
+            &mut HashMap::new(),
+            &mut undo,
+        );
+        let outcome = AddCoinsOutcome::of(&undo);
+        self.finish_blocks(undo);
+        Ok(outcome)
+    }
+
+    /// Adds the coin diffs of many consecutive blocks of the given network at once, each given as its height, its new coins, and its spent coins. This is like calling [Wallet::add_coins] for each block, but faster when syncing from scratch: coins that are created and then spent within the range are never stored at all. The first block must be exactly one above the current height of the wallet, and the rest must follow without gaps. On error, none of the diffs are applied.
+    ///
+    /// The whole range counts as a single block for [Wallet::rollback_to], so it can only be rolled back as a whole. Likewise, the returned outcome covers the whole range, leaving out coins that were created and spent within it.
+    pub fn add_coins_range(
+        &mut self,
+        netid: NetID,
+        diffs: impl IntoIterator<Item = (BlockHeight, Vec<(CoinID, CoinData)>, Vec<CoinID>)>,
+    ) -> Result<AddCoinsOutcome, AddCoinsError> {
+        if netid != self.netid {
+            return Err(AddCoinsError::WrongNetwork);
+        }
+        let diffs: Vec<_> = diffs.into_iter().collect();
+        let mut expected_height = self.height;
+        for (height, new_coins, _) in diffs.iter() {
+            expected_height += BlockHeight(1);
+            if *height != expected_height {
+                return Err(AddCoinsError::BadHeight);
+            }
+            if new_coins
+                .iter()
+                .any(|(_, coin_data)| coin_data.covhash != self.address)
+            {
                 return Err(AddCoinsError::WrongAddress);
             }
-            accum.insert(coin_id, CoinDataHeight { coin_data, height });
         }
+        if diffs.is_empty() {
+            return Ok(AddCoinsOutcome::default());
+        }
+
+        let mut undo = BlockUndo {
+            prev_height: self.height,
+            height: expected_height,
+            added: vec![],
+            removed: vec![],
+            confirmed_pending: vec![],
+        };
+        let spent_in_range: HashSet<CoinID> = diffs
+            .iter()
+            .flat_map(|(_, _, spent_coins)| spent_coins.iter().copied())
+            .collect();
+        let mut created_in_range = HashMap::new();
+        for (height, new_coins, spent_coins) in diffs {
+            self.apply_block(
+                height,
+                new_coins,
+                &spent_coins.into_iter().collect(),
+                &spent_in_range,
+                &mut created_in_range,
+                &mut undo,
+            );
+        }
+        let outcome = AddCoinsOutcome::of(&undo);
+        self.finish_blocks(undo);
+        Ok(outcome)
+    }
+
+    /// Applies the coin diffs of one block, whose new coins must all belong to this wallet, noting how to undo them in `undo`. New coins in `spent_later` get spent again before the diffs being applied end, so they are never stored; `created` remembers them so that the history can tell what spending them cost.
+    fn apply_block(
+        &mut self,
+        height: BlockHeight,
+        new_coins: Vec<(CoinID, CoinData)>,
+        spent_coins: &HashSet<CoinID>,
+        spent_later: &HashSet<CoinID>,
+        created: &mut HashMap<CoinID, CoinDataHeight>,
+        undo: &mut BlockUndo,
+    ) {
+        let mut accum = HashMap::new();
+        // transactions that created coins here, even coins of untracked denominations, are confirmed
+        let mut confirmed_txs = BTreeSet::new();
+        for (coin_id, coin_data) in new_coins {
+            confirmed_txs.insert(coin_id.txhash);
+            if self.is_tracked(coin_data.denom) {
+                accum.insert(coin_id, CoinDataHeight { coin_data, height });
+            }
+        }
+        // so are pending transactions whose inputs got spent, even if none of their outputs come back to us. If something else spent them instead, the pending transaction can never confirm, so it's just as finished.
+        confirmed_txs.extend(
+            self.pending_outgoing
+                .iter()
+                .filter(|(_, tx)| tx.inputs.iter().any(|c| spent_coins.contains(c)))
+                .map(|(txhash, _)| *txhash),
+        );
+        created.extend(
+            accum
+                .iter()
+                .filter(|(k, _)| spent_later.contains(k))
+                .map(|(k, v)| (*k, v.clone())),
+        );
 
-        // update the wallet itself
+        // work out how each transaction changed the wallet, before pending transactions get cleared
+        let mut net_changes: BTreeMap<TxHash, BTreeMap<Denom, i128>> = BTreeMap::new();
+        for (k, v) in accum.iter() {
+            let change = net_changes
+                .entry(k.txhash)
+                .or_default()
+                .entry(v.coin_data.denom)
+                .or_default();
+            *change = change.saturating_add(signed_value(v.coin_data.value));
+        }
+        for k in spent_coins.iter() {
+            if let Some(v) = self.confirmed_utxos.get(k).or_else(|| created.get(k)) {
+                let spender = self
+                    .pending_outgoing
+                    .iter()
+                    .find(|(_, tx)| tx.inputs.contains(k))
+                    .map(|(txhash, _)| *txhash)
+                    .unwrap_or(TxHash(Default::default()));
+                let change = net_changes
+                    .entry(spender)
+                    .or_default()
+                    .entry(v.coin_data.denom)
+                    .or_default();
+                *change = change.saturating_sub(signed_value(v.coin_data.value));
+            }
+        }
+        for (txhash, mut net_change) in net_changes {
+            net_change.retain(|_, change| *change != 0);
+            self.history.push(HistoryEntry {
+                txhash,
+                height,
+                net_change,
+                kind: self
+                    .pending_outgoing
+                    .get(&txhash)
+                    .map(|tx| tx.kind)
+                    .unwrap_or(TxKind::Normal),
+            });
+        }
+
+        // update the wallet itself, remembering how to undo it
+        for txhash in confirmed_txs {
+            // the originating transaction of these coins must no longer be pending
+            if let Some(tx) = self.pending_outgoing.remove(&txhash) {
+                self.pending_heights.remove(&txhash);
+                undo.confirmed_pending.push(tx);
+            }
+        }
         for (k, v) in accum {
-            // the originating transaction of this coin must no longer be pending
-            self.pending_outgoing.remove(&k.txhash);
-            self.confirmed_utxos.insert(k, v);
+            // a coin that is spent again right away leaves nothing to undo
+            if spent_later.contains(&k) {
+                continue;
+            }
+            if self.confirmed_utxos.insert(k, v).is_none() {
+                undo.added.push(k);
+            }
         }
         for k in spent_coins {
-            self.confirmed_utxos.remove(&k);
+            if let Some(v) = self.confirmed_utxos.remove(k) {
+                // spent coins can't be spent again, so there's nothing left to reserve
+                self.reservations.forget(k);
+                undo.removed.push((*k, v));
+            }
         }
-        self.height = height;
-        Ok(())
     }
 
-    /// Reset the wallet to a certain set of coins.
+    /// Remembers how to undo newly applied blocks, and moves the wallet up to the height they end at.
+    fn finish_blocks(&mut self, undo: BlockUndo) {
+        self.height = undo.height;
+        self.recent_blocks.push_back(undo);
+        while self.recent_blocks.len() > MAX_ROLLBACK_DEPTH {
+            // coins spent in blocks that can no longer be rolled back are gone for good, and so is whatever we noted about them
+            if let Some(forgotten) = self.recent_blocks.pop_front() {
+                self.forget_coins(forgotten.removed.iter().map(|(coin, _)| coin));
+            }
+        }
+    }
+
+    /// Drops the maturities, freezes and reservations of the given coins, once they have left the wallet for good.
+    pub(crate) fn forget_coins<'a>(&mut self, coins: impl IntoIterator<Item = &'a CoinID>) {
+        for coin in coins {
+            self.maturity.remove(coin);
+            self.frozen.remove(coin);
+            self.reservations.forget(coin);
+        }
+    }
+
+    /// Returns a copy of the wallet for handing to a monitoring service that only watches the address. A [Wallet] never holds keys, so any copy is already unable to sign; the snapshot additionally drops the pending transactions, since a watcher can't know whether they were ever broadcast and should only go by what's confirmed, along with any reservations. Everything else, including the confirmed coins, the height and the history, is kept, so [Wallet::balances] stays the same while [Wallet::available_balances] and [Wallet::spendable_balances] no longer leave out the coins those transactions were spending.
+    pub fn watch_only_snapshot(&self) -> Wallet {
+        let mut snapshot = self.clone();
+        snapshot.pending_outgoing.clear();
+        snapshot.pending_heights.clear();
+        snapshot.reservations = Reservations::default();
+        snapshot
+    }
+
+    /// Reset the wallet to a certain set of coins, taken from the given network.
     pub fn full_reset(
         &mut self,
+        netid: NetID,
         latest_height: BlockHeight,
         confirmed_utxos: impl IntoIterator<Item = (CoinID, CoinDataHeight)>,
     ) -> Result<(), AddCoinsError> {
-        let confirmed_utxos: BTreeMap<CoinID, CoinDataHeight> =
-            confirmed_utxos.into_iter().collect();
+        if netid != self.netid {
+            return Err(AddCoinsError::WrongNetwork);
+        }
+        let confirmed_utxos: BTreeMap<CoinID, CoinDataHeight> = confirmed_utxos
+            .into_iter()
+            .filter(|(_, cdh)| self.is_tracked(cdh.coin_data.denom))
+            .collect();
 
         // Verify that the inputs have the correct address
         for (_, coin_data_height) in confirmed_utxos.iter() {
@@ -104,8 +606,14 @@ impl Wallet {
         }
 
         self.height = latest_height;
+        self.maturity
+            .retain(|coin, _| confirmed_utxos.contains_key(coin));
+        self.frozen
+            .retain(|coin| confirmed_utxos.contains_key(coin));
         self.confirmed_utxos = confirmed_utxos;
         self.pending_outgoing.clear();
+        self.pending_heights.clear();
+        self.recent_blocks.clear();
         Ok(())
     }
 
@@ -116,40 +624,546 @@ impl Wallet {
         signer: &S,
         fee_multiplier: u128,
     ) -> Result<Transaction, PrepareTxError<S::Error>> {
-        // Exponentially increase the fees until we either run out of money, or we have enough fees.
-        for power in 0.. {
-            let fee = CoinValue(1.1f64.powi(power) as _);
-            // Tally up the total outputs
+        if args.check_signer && !self.matches_signer(signer) {
+            return Err(PrepareTxError::SignerMismatch);
+        }
+        let preview =
+            self.assemble_tx(&args, signer.covenant(), signer.sig_size(), fee_multiplier)?;
+        let spent = preview.inputs.into_iter().collect();
+        Ok(sign_all(
+            preview.transaction,
+            signer,
+            &args.input_unlock_args,
+            &spent,
+        )?)
+    }
+
+    /// Runs the whole input selection and fee computation of [Wallet::prepare_tx] for a signer with the given signature size and covenant, without signing anything. This lets callers show the fee, inputs, and change before involving the signer, which may be a hardware device that prompts the user.
+    pub fn simulate_tx(
+        &self,
+        args: &PrepareTxArgs,
+        sig_size: usize,
+        covenant: Bytes,
+        fee_multiplier: u128,
+    ) -> Result<TxPreview, PrepareTxError<Infallible>> {
+        self.assemble_tx(args, covenant, sig_size, fee_multiplier)
+    }
+
+    /// Estimates the fee of the transaction that [Wallet::prepare_tx] would prepare, without needing a signer: only the sizes of the signer's signatures and covenant are needed. This runs the same input selection, so the estimate accounts for the number of inputs. The estimate leaves out the cost of executing the covenant, which only the covenant itself can tell, so the fee that [Wallet::prepare_tx] ends up paying may be slightly higher.
+    pub fn estimate_fee(
+        &self,
+        args: &PrepareTxArgs,
+        sig_size: usize,
+        covenant_len: usize,
+        fee_multiplier: u128,
+    ) -> Result<CoinValue, PrepareTxError<Infallible>> {
+        Ok(self
+            .simulate_tx(
+                args,
+                sig_size,
+                Bytes::from(vec![0; covenant_len]),
+                fee_multiplier,
+            )?
+            .fee)
+    }
+
+    /// Estimates the weight of the transaction that [Wallet::prepare_tx] would prepare, for a signer with the given signature and covenant sizes, without working out its fee. This is what UIs can show as the size of the transaction. Note that Mel weighs each output at an extra 1000 and discounts each input by 1000, since spending coins shrinks the coin set, so adding inputs makes a transaction *lighter*, down to a weight of zero. Since no fee is paid, inputs are only picked to cover the outputs; paying the fee may take more. Like [Wallet::estimate_fee], this leaves out the cost of executing the covenant.
+    pub fn estimate_weight(
+        &self,
+        args: &PrepareTxArgs,
+        sig_size: usize,
+        covenant_len: usize,
+    ) -> Result<u128, PrepareTxError<Infallible>> {
+        Ok(self
+            .simulate_tx(args, sig_size, Bytes::from(vec![0; covenant_len]), 0)?
+            .transaction
+            .weight(melvm::covenant_weight_from_bytes))
+    }
+
+    /// Previews the change outputs that preparing the given transaction would create if it paid exactly the given fee, such as the fee from [Wallet::estimate_fee], so that UIs can warn about unexpected change before anything is signed. Coins are picked as [Wallet::prepare_tx] would pick them to cover the outputs and that fee.
+    ///
+    /// Since the fee is taken as exact, like [PrepareTxArgs::fee_override], MEL change below the dust threshold shows up as an output rather than being folded into the fee.
+    pub fn preview_change(
+        &self,
+        args: &PrepareTxArgs,
+        fee: CoinValue,
+    ) -> Result<Vec<CoinData>, PrepareTxError<Infallible>> {
+        let args = PrepareTxArgs {
+            fee_override: Some(fee),
+            ..args.clone()
+        };
+        let mut outputs = self.simulate_tx(&args, 0, Bytes::new(), 0)?.outputs;
+        // the outputs may have been shuffled, so take out the required ones by value
+        for required in args.outputs.iter() {
+            if let Some(idx) = outputs.iter().position(|output| output == required) {
+                outputs.remove(idx);
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Returns the inputs that preparing the given transaction would spend if it paid exactly the given fee, including the external inputs from [PrepareTxArgs::inputs], without building a signed transaction. This lets callers fetch whatever they need about those coins, such as their Merkle proofs, ahead of time.
+    pub fn required_inputs_for(
+        &self,
+        args: &PrepareTxArgs,
+        fee: CoinValue,
+    ) -> Result<Vec<(CoinID, CoinDataHeight)>, PrepareTxError<Infallible>> {
+        let args = PrepareTxArgs {
+            fee_override: Some(fee),
+            ..args.clone()
+        };
+        Ok(self.simulate_tx(&args, 0, Bytes::new(), 0)?.inputs)
+    }
+
+    /// Prepares a transaction without signing it, for wallets that can't sign, such as watch-only wallets monitoring cold storage. The transaction has zeroed-out placeholder signatures of the given size, so that its fee is already correct once it's signed elsewhere (for example, on an air-gapped machine) by a signer with the given covenant.
+    pub fn prepare_unsigned(
+        &self,
+        args: &PrepareTxArgs,
+        sig_size: usize,
+        covenant: Bytes,
+        fee_multiplier: u128,
+    ) -> Result<Transaction, PrepareTxError<Infallible>> {
+        Ok(self
+            .simulate_tx(args, sig_size, covenant, fee_multiplier)?
+            .transaction)
+    }
+
+    /// Like [Wallet::prepare_tx], but signs with an [AsyncSigner], awaiting the signature of each input in turn. The signer is told which coin each input spends, with [AsyncSigner::async_sign_with_context].
+    pub async fn prepare_tx_async<S: AsyncSigner>(
+        &self,
+        args: PrepareTxArgs,
+        signer: &S,
+        fee_multiplier: u128,
+    ) -> Result<Transaction, PrepareTxError<S::Error>> {
+        let covenant = signer.async_covenant();
+        if args.check_signer && self.address != tmelcrypt::hash_single(&covenant).into() {
+            return Err(PrepareTxError::SignerMismatch);
+        }
+        let preview = self.assemble_tx(&args, covenant, signer.async_sig_size(), fee_multiplier)?;
+        let mut tx = preview.transaction;
+        for (i, (coin_id, cdh)) in preview.inputs.iter().enumerate() {
+            if !args.input_unlock_args.contains_key(coin_id) {
+                tx = signer.async_sign_with_context(&tx, i, cdh).await?;
+            }
+        }
+        Ok(tx)
+    }
+
+    /// Returns the largest amount of the given denomination that can be sent to a single recipient, after paying fees. For MEL, the fee is taken out of the sent amount; for other denominations, the whole balance can be sent as long as the MEL coins can pay the fee. Coins locked by pending transactions are not counted.
+    pub fn max_spendable(
+        &self,
+        denom: Denom,
+        signer: &impl Signer,
+        fee_multiplier: u128,
+    ) -> CoinValue {
+        let (coins, values): (Vec<CoinID>, Vec<CoinValue>) = self
+            .spendable_utxos()
+            .filter(|(_, cdh)| cdh.coin_data.denom == denom)
+            .map(|(k, cdh)| (*k, cdh.coin_data.value))
+            .unzip();
+        let total = saturating_sum(values);
+        let recipient = CoinData {
+            covhash: self.address,
+            value: total,
+            denom,
+            additional_data: Bytes::new(),
+        };
+        if denom != FEE_DENOM {
+            let args = PrepareTxArgs {
+                outputs: vec![recipient],
+                ..Default::default()
+            };
+            return match self.assemble_tx::<Infallible>(
+                &args,
+                signer.covenant(),
+                signer.sig_size(),
+                fee_multiplier,
+            ) {
+                Ok(_) if total > CoinValue(0) => total,
+                _ => CoinValue(0),
+            };
+        }
+
+        self.assemble_sweep(
+            coins,
+            total,
+            BTreeMap::new(),
+            signer.covenant(),
+            signer.sig_size(),
+            fee_multiplier,
+        )
+        .map(|tx| tx.outputs[0].value)
+        .unwrap_or_default()
+    }
+
+    /// Prepares a transaction that consolidates up to `max_inputs` of the smallest coins of the given denomination into a single coin back to this wallet. This lets wallets with many small coins proactively defragment, so that later transactions need fewer inputs.
+    ///
+    /// When consolidating MEL, the fee is taken out of the consolidated coin. For other denominations, the fee is paid by MEL coins as usual.
+    pub fn prepare_consolidation<S: Signer>(
+        &self,
+        denom: Denom,
+        max_inputs: usize,
+        signer: &S,
+        fee_multiplier: u128,
+    ) -> Result<Transaction, PrepareTxError<S::Error>> {
+        let mut coins: Vec<(CoinID, CoinValue)> = self
+            .spendable_utxos()
+            .filter(|(_, cdh)| cdh.coin_data.denom == denom)
+            .map(|(k, cdh)| (*k, cdh.coin_data.value))
+            .collect();
+        coins.sort_by_key(|(_, value)| *value);
+        coins.truncate(max_inputs);
+        if coins.len() < 2 {
+            return Err(PrepareTxError::NothingToConsolidate(denom));
+        }
+        let (coins, values): (Vec<CoinID>, Vec<CoinValue>) = coins.into_iter().unzip();
+        let total = saturating_sum(values);
+
+        let assembled = if denom == FEE_DENOM {
+            self.assemble_sweep(
+                coins,
+                total,
+                BTreeMap::new(),
+                signer.covenant(),
+                signer.sig_size(),
+                fee_multiplier,
+            )
+            .map_err(|needed| PrepareTxError::InsufficientFunds {
+                denom: FEE_DENOM,
+                needed,
+                available: total,
+            })?
+        } else {
+            // picking the smallest coins first until the total is covered selects exactly the coins above
+            let args = PrepareTxArgs {
+                outputs: vec![CoinData {
+                    covhash: self.address,
+                    value: total,
+                    denom,
+                    additional_data: Bytes::new(),
+                }],
+                coin_selection: CoinSelectionStrategy::SmallestFirst,
+                ..Default::default()
+            };
+            self.assemble_tx(&args, signer.covenant(), signer.sig_size(), fee_multiplier)?
+                .transaction
+        };
+        let spent = assembled
+            .inputs
+            .iter()
+            .filter_map(|coin_id| Some((*coin_id, self.confirmed_utxos.get(coin_id)?.clone())))
+            .collect();
+        Ok(sign_all(assembled, signer, &BTreeMap::new(), &spent)?)
+    }
+
+    /// Prepares a transaction that fans out `count` coins of `piece_value` each, all to `recipient`, with any change going back to this wallet as usual. Having many equally-sized coins helps with airdrops, and lets later transactions spend coins in parallel.
+    ///
+    /// Fails with [PrepareTxError::TooLarge] if the assembled transaction weighs more than [MAX_SPLIT_WEIGHT], which is what too large a `count` runs into.
+    pub fn prepare_split<S: Signer>(
+        &self,
+        recipient: Address,
+        denom: Denom,
+        piece_value: CoinValue,
+        count: usize,
+        signer: &S,
+        fee_multiplier: u128,
+    ) -> Result<Transaction, PrepareTxError<S::Error>> {
+        let piece = CoinData {
+            covhash: recipient,
+            value: piece_value,
+            denom,
+            additional_data: Bytes::new(),
+        };
+        let args = PrepareTxArgs {
+            outputs: vec![piece; count],
+            max_weight: Some(MAX_SPLIT_WEIGHT),
+            ..Default::default()
+        };
+        self.prepare_tx(args, signer, fee_multiplier)
+    }
+
+    /// Rebuilds a stuck transaction, prepared earlier by this wallet, so that it pays the given higher fee. The replacement spends all the inputs of the old transaction, so that at most one of them can confirm, plus more coins if needed. It keeps the old outputs, except that outputs back to this wallet's address are taken to be change and recomputed. Record the replacement with [Wallet::replace_pending] once it's broadcast.
+    ///
+    /// Fails with [PrepareTxError::FeeNotHigher] unless the new fee is higher than the old one, and with [PrepareTxError::BadExternalInput] if the old transaction spent coins that aren't in this wallet.
+    pub fn bump_fee<S: Signer>(
+        &self,
+        old: &Transaction,
+        new_fee: CoinValue,
+        signer: &S,
+        fee_multiplier: u128,
+    ) -> Result<Transaction, PrepareTxError<S::Error>> {
+        if new_fee <= old.fee {
+            return Err(PrepareTxError::FeeNotHigher {
+                old: old.fee,
+                new: new_fee,
+            });
+        }
+        let required = old
+            .inputs
+            .iter()
+            .map(|coin| {
+                self.confirmed_utxos
+                    .get(coin)
+                    .map(|cdh| (*coin, cdh.clone()))
+                    .ok_or(PrepareTxError::BadExternalInput(*coin))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let signer_covenant = signer.covenant();
+        let args = PrepareTxArgs {
+            kind: old.kind,
+            outputs: old
+                .outputs
+                .iter()
+                .filter(|output| output.covhash != self.address)
+                .cloned()
+                .collect(),
+            covenants: old
+                .covenants
+                .iter()
+                .filter(|covenant| **covenant != signer_covenant)
+                .cloned()
+                .collect(),
+            data: old.data.clone(),
+            fee_override: Some(new_fee),
+            ..Default::default()
+        };
+        let preview = self.assemble_tx_with(
+            &args,
+            &required,
+            signer_covenant,
+            signer.sig_size(),
+            fee_multiplier,
+        )?;
+        let spent = preview.inputs.into_iter().collect();
+        Ok(sign_all(
+            preview.transaction,
+            signer,
+            &BTreeMap::new(),
+            &spent,
+        )?)
+    }
+
+    /// Assembles an unsigned transaction spending the given coins into a single MEL coin back to this wallet, worth the `total` MEL of the coins with the fee taken out, followed by one coin back to this wallet for each of the `others` totals of other denominations. Fails with the fee needed if the MEL can't even cover it.
+    pub(crate) fn assemble_sweep(
+        &self,
+        coins: Vec<CoinID>,
+        total: CoinValue,
+        others: BTreeMap<Denom, CoinValue>,
+        covenant: Bytes,
+        sig_size: usize,
+        fee_multiplier: u128,
+    ) -> Result<Transaction, CoinValue> {
+        let mut tx = Transaction {
+            kind: TxKind::Normal,
+            sigs: std::iter::repeat_n(Bytes::from(vec![0; sig_size]), coins.len()).collect(),
+            inputs: coins,
+            outputs: std::iter::once((FEE_DENOM, total))
+                .chain(others)
+                .map(|(denom, value)| CoinData {
+                    covhash: self.address,
+                    value,
+                    denom,
+                    additional_data: Bytes::new(),
+                })
+                .collect(),
+            fee: CoinValue(0),
+            covenants: vec![covenant],
+            data: Bytes::new(),
+        };
+        loop {
+            let base_fee = tx.base_fee(fee_multiplier, 0, melvm::covenant_weight_from_bytes);
+            if base_fee <= tx.fee {
+                return Ok(tx);
+            }
+            tx.fee = base_fee;
+            tx.outputs[0].value = total.checked_sub(base_fee).ok_or(base_fee)?;
+        }
+    }
+
+    /// Selects inputs, computes the fee, and assembles a transaction with the given covenant for the wallet's own coins, without signing it. The assembled transaction has zeroed-out placeholder signatures of the given size.
+    fn assemble_tx<E: Error>(
+        &self,
+        args: &PrepareTxArgs,
+        covenant: Bytes,
+        sig_size: usize,
+        fee_multiplier: u128,
+    ) -> Result<TxPreview, PrepareTxError<E>> {
+        self.assemble_tx_with(args, &[], covenant, sig_size, fee_multiplier)
+    }
+
+    /// Like [Wallet::assemble_tx], but always spends the given coins of the wallet, even if they are locked by a pending transaction, on top of whatever else is picked.
+    fn assemble_tx_with<E: Error>(
+        &self,
+        args: &PrepareTxArgs,
+        required: &[(CoinID, CoinDataHeight)],
+        covenant: Bytes,
+        sig_size: usize,
+        fee_multiplier: u128,
+    ) -> Result<TxPreview, PrepareTxError<E>> {
+        // External inputs must really be external (unless coin control lets the caller pick our own spendable coins), must be actual coins, and must not already be spent by one of our pending transactions
+        let pending_inputs = self.pending_inputs();
+        for (coin_id, cdh) in args.inputs.iter() {
+            let own_ok = !self.confirmed_utxos.contains_key(coin_id)
+                || (args.coin_control && self.is_spendable(coin_id, &pending_inputs));
+            if !own_ok || cdh.coin_data.value == CoinValue(0) || pending_inputs.contains(coin_id) {
+                return Err(PrepareTxError::BadExternalInput(*coin_id));
+            }
+        }
+        // zero-value outputs are almost always a bug, except that minting a new token may well start with no supply
+        for (i, output) in args.outputs.iter().enumerate() {
+            if output.value == CoinValue(0) && output.denom != Denom::NewCustom {
+                return Err(PrepareTxError::ZeroOutput(i));
+            }
+        }
+        // MEL outputs worth less than the dust threshold cost more to spend than they're worth
+        for output in args.outputs.iter() {
+            if output.denom == FEE_DENOM && output.value < args.dust_threshold {
+                return Err(PrepareTxError::DustOutput(output.value));
+            }
+        }
+
+        // Order our own coins once, so that every round of the fee search picks from the same sequence. Coin control picks none at all.
+        let mut candidates: Vec<(CoinID, CoinDataHeight)> = self
+            .confirmed_utxos
+            .iter()
+            .filter(|(k, _)| self.is_spendable(k, &pending_inputs))
+            .filter(|_| !args.coin_control)
+            .filter(|(k, _)| !required.iter().any(|(required, _)| required == *k))
+            .filter(|(k, _)| self.confirmations(k).unwrap_or_default() >= args.min_confirmations.0)
+            .map(|(k, v)| (*k, v.clone()))
+            .collect();
+        // with nothing at all to spend, outputs that need funding can't be paid for, so report the denomination of the first of them. How big the fee would be isn't known yet, so for MEL, only the outputs and any explicit fee count as needed. Otherwise, the fee may well be zero, as when estimating weight, so carry on.
+        if candidates.is_empty() && required.is_empty() && args.inputs.is_empty() {
+            if let Some(short) = args
+                .outputs
+                .iter()
+                .find(|output| needs_inputs(args.kind, output))
+            {
+                let fee = args.fee_override.filter(|_| short.denom == FEE_DENOM);
+                return Err(PrepareTxError::InsufficientFunds {
+                    denom: short.denom,
+                    needed: saturating_sum(
+                        args.outputs
+                            .iter()
+                            .filter(|output| {
+                                output.denom == short.denom && needs_inputs(args.kind, output)
+                            })
+                            .map(|output| output.value)
+                            .chain(fee),
+                    ),
+                    available: CoinValue(0),
+                });
+            }
+        }
+        args.coin_selection.order(&mut candidates);
+        // MEL dust goes last (the sort is stable), so it's only picked when nothing else is enough
+        candidates.sort_by_key(|(_, cdh)| {
+            cdh.coin_data.denom == FEE_DENOM && cdh.coin_data.value < args.dust_threshold
+        });
+        // What an extra change output costs in fees. Branch-and-bound selection overpays the fee by up to this much rather than creating MEL change.
+        let change_cost = {
+            let change = CoinData {
+                covhash: self.address,
+                value: CoinValue(0),
+                denom: FEE_DENOM,
+                additional_data: args.change_data.clone(),
+            };
+            let without = Transaction::default();
+            let with = Transaction {
+                outputs: vec![change],
+                ..Transaction::default()
+            };
+            let base_fee = |tx: &Transaction| {
+                tx.base_fee(fee_multiplier, 0, melvm::covenant_weight_from_bytes)
+            };
+            base_fee(&with) - base_fee(&without)
+        };
+
+        // Raise the fee to the base fee of the transaction it produces, until the fee covers its own transaction. Since the fee only ever grows, this reaches a fixed point (usually within 2-3 rounds) or runs out of money. An explicit fee is used as-is.
+        let mut fee = args.fee_override.unwrap_or_default();
+        loop {
+            // Tally up the total outputs. Newly minted tokens (Denom::NewCustom) don't need any inputs.
             let mut inmoney_needed: BTreeMap<Denom, CoinValue> =
                 args.outputs
                     .iter()
                     .fold(BTreeMap::new(), |mut map, output| {
-                        if output.denom != Denom::NewCustom
-                            && !(args.kind == TxKind::DoscMint && output.denom == Denom::Erg)
-                        {
-                            *map.entry(output.denom).or_default() += output.value;
+                        if needs_inputs(args.kind, output) {
+                            add_saturating(map.entry(output.denom).or_default(), output.value);
                         }
                         map
                     });
-            *inmoney_needed.entry(Denom::Mel).or_default() += fee;
+            add_saturating(inmoney_needed.entry(FEE_DENOM).or_default(), fee);
             // pick out input UTXOs until we have enough, then construct a Transaction
-            let mut to_spend = args.inputs.clone();
+            let mut to_spend: Vec<(CoinID, CoinDataHeight)> =
+                required.iter().chain(args.inputs.iter()).cloned().collect();
             let mut inmoney_actual: BTreeMap<Denom, CoinValue> =
                 to_spend.iter().fold(BTreeMap::new(), |mut map, (_, cdh)| {
-                    *map.entry(cdh.coin_data.denom).or_default() += cdh.coin_data.value;
+                    add_saturating(
+                        map.entry(cdh.coin_data.denom).or_default(),
+                        cdh.coin_data.value,
+                    );
 
                     map
                 });
-            let mut touched_coin_count = 0;
             for (denom, needed) in inmoney_needed.iter() {
-                for (in_coinid, in_cdh) in self
-                    .spendable_utxos()
+                let have = inmoney_actual.get(denom).copied().unwrap_or_default();
+                let pool: Vec<&(CoinID, CoinDataHeight)> = candidates
+                    .iter()
                     .filter(|(_, v)| &v.coin_data.denom == denom)
-                {
+                    .collect();
+                // only MEL excess can be absorbed into the fee; other denoms must match exactly
+                let tolerance = match args.coin_selection {
+                    CoinSelectionStrategy::BranchAndBound if *denom == FEE_DENOM => {
+                        Some(change_cost)
+                    }
+                    // MEL change below the dust threshold goes to the fee anyway
+                    CoinSelectionStrategy::Tidy
+                        if *denom == FEE_DENOM && args.fee_override.is_none() =>
+                    {
+                        Some(CoinValue(args.dust_threshold.0.saturating_sub(1)))
+                    }
+                    CoinSelectionStrategy::BranchAndBound | CoinSelectionStrategy::Tidy => {
+                        Some(CoinValue(0))
+                    }
+                    _ => None,
+                };
+                if let (Some(tolerance), true) = (tolerance, have < *needed) {
+                    let values: Vec<CoinValue> =
+                        pool.iter().map(|(_, v)| v.coin_data.value).collect();
+                    if let Some(chosen) = select_exact(&values, *needed - have, tolerance) {
+                        for idx in chosen {
+                            let (in_coinid, in_cdh) = pool[idx];
+                            to_spend.push((*in_coinid, in_cdh.clone()));
+                            add_saturating(
+                                inmoney_actual.entry(*denom).or_default(),
+                                in_cdh.coin_data.value,
+                            );
+                        }
+                        continue;
+                    }
+                }
+                if args.coin_selection == CoinSelectionStrategy::Tidy && have < *needed {
+                    // no exact cover, so settle for a single change output. The pool is largest first, so the last coin that covers everything is the smallest such coin
+                    if let Some((in_coinid, in_cdh)) = pool
+                        .iter()
+                        .rev()
+                        .find(|(_, v)| v.coin_data.value >= *needed - have)
+                    {
+                        to_spend.push((*in_coinid, in_cdh.clone()));
+                        add_saturating(
+                            inmoney_actual.entry(*denom).or_default(),
+                            in_cdh.coin_data.value,
+                        );
+                        continue;
+                    }
+                }
+                for (in_coinid, in_cdh) in pool {
                     if inmoney_actual.get(denom).copied().unwrap_or_default() < *needed {
-                        touched_coin_count += 1;
                         to_spend.push((*in_coinid, in_cdh.clone()));
-                        *inmoney_actual.entry(*denom).or_default() += in_cdh.coin_data.value;
+                        add_saturating(
+                            inmoney_actual.entry(*denom).or_default(),
+                            in_cdh.coin_data.value,
+                        );
                     } else {
                         break;
                     }
@@ -157,103 +1171,499 @@ impl Wallet {
             }
             // produce change outputs
             let mut outputs = args.outputs.clone();
-            if !inmoney_actual.contains_key(&Denom::Mel) {
-                return Err(PrepareTxError::InsufficientFunds(Denom::Mel)); // you always need MEL to pay the transaction fee
+            let mut change = vec![];
+            for (denom, needed) in inmoney_needed.iter() {
+                // the MEL needed always includes the transaction fee
+                let available = inmoney_actual.get(denom).copied().unwrap_or_default();
+                if available < *needed {
+                    return Err(PrepareTxError::InsufficientFunds {
+                        denom: *denom,
+                        needed: *needed,
+                        available,
+                    });
+                }
             }
 
+            let mut actual_fee = fee;
             for (denom, inmoney) in &inmoney_actual {
-                if let Some(change_value) =
-                    inmoney.checked_sub(inmoney_needed.get(denom).copied().unwrap_or(CoinValue(0)))
+                let needed = inmoney_needed.get(denom).copied().unwrap_or(CoinValue(0));
+                // every shortfall was already reported above, so there's never less than needed
+                let change_value = *inmoney - needed;
+                if args.fee_override.is_none()
+                    && *denom == FEE_DENOM
+                    && (change_value < args.dust_threshold
+                        || (args.coin_selection == CoinSelectionStrategy::BranchAndBound
+                            && change_value <= change_cost))
                 {
-                    if change_value > CoinValue(0) {
-                        outputs.push(CoinData {
-                            covhash: self.address,
+                    // cheaper to overpay the fee than to create a (dust) change output
+                    add_saturating(&mut actual_fee, change_value);
+                } else if change_value > CoinValue(0) {
+                    // split the change into equal-ish parts, none of which may be MEL dust
+                    let max_parts = if *denom == FEE_DENOM {
+                        change_value.0 / args.dust_threshold.0.max(1)
+                    } else {
+                        change_value.0
+                    };
+                    let parts = if args.change_isolation {
+                        1
+                    } else {
+                        (args.split_change as u128).min(max_parts).max(1)
+                    };
+                    for i in 0..parts {
+                        let remainder = if i == 0 { change_value.0 % parts } else { 0 };
+                        change.push(CoinData {
+                            covhash: args.change_address.unwrap_or(self.address),
                             denom: *denom,
-                            value: change_value,
-                            additional_data: Bytes::new(),
+                            value: CoinValue(change_value.0 / parts + remainder),
+                            additional_data: args.change_data.clone(),
                         });
                     }
-                } else {
-                    return Err(PrepareTxError::InsufficientFunds(*denom));
                 }
             }
 
+            // the signer's covenant is needed for the wallet's own coins; extra covenants unlock out-of-wallet inputs
+            let mut covenants = vec![];
+            if to_spend
+                .iter()
+                .any(|(_, cdh)| cdh.coin_data.covhash == self.address)
+            {
+                covenants.push(covenant.clone());
+            }
+            for covenant in args.covenants.iter() {
+                if !covenants.contains(covenant) {
+                    covenants.push(covenant.clone());
+                }
+            }
+
+            // canonically order inputs and outputs, so the transaction doesn't leak how coins were picked or which output is change. Outputs of special transactions have positional meaning, so those are left alone.
+            to_spend.sort_unstable_by_key(|(coin_id, _)| *coin_id);
+            if !args.change_isolation {
+                outputs.append(&mut change);
+            }
+            if args.kind == TxKind::Normal {
+                outputs.sort_by(|a, b| {
+                    (a.denom, a.value, a.covhash, &a.additional_data).cmp(&(
+                        b.denom,
+                        b.value,
+                        b.covhash,
+                        &b.additional_data,
+                    ))
+                });
+            }
+            // isolated change goes after everything else, so it can be found by position
+            outputs.extend(change);
+
             // assemble the transaction
-            let mut assembled = Transaction {
+            let assembled = Transaction {
                 kind: args.kind,
                 inputs: to_spend.iter().map(|s| s.0).collect(),
                 outputs,
-                fee,
-                covenants: std::iter::repeat(signer.covenant())
-                    .take(to_spend.len())
-                    .collect(),
-                data: args.data.clone(),
-                sigs: std::iter::repeat(Bytes::from(vec![0; signer.sig_size()]))
-                    .take(to_spend.len())
+                fee: actual_fee,
+                covenants,
+                data: match args.locktime {
+                    Some(height) => encode_locktime(height, &args.data),
+                    None => args.data.clone(),
+                },
+                sigs: to_spend
+                    .iter()
+                    .map(|(coin_id, _)| {
+                        args.input_unlock_args
+                            .get(coin_id)
+                            .cloned()
+                            .unwrap_or_else(|| Bytes::from(vec![0; sig_size]))
+                    })
                     .collect(),
             };
-            if assembled
-                .base_fee(
-                    fee_multiplier,
-                    args.fee_ballast as u128,
-                    melvm::covenant_weight_from_bytes,
-                )
-                .0
-                <= fee.0
-            {
-                // println!("FEE = {}", fee);
-                assembled.sigs.clear();
-                let signed =
-                    (0..to_spend.len()).try_fold(assembled, |tx, i| signer.sign(&tx, i))?;
-                return Ok(signed);
+            let weight = assembled.weight(melvm::covenant_weight_from_bytes);
+            let frac_ballast = weight
+                .saturating_mul(args.fee_ballast_ppm.min(MAX_FEE_BALLAST_PPM) as u128)
+                / 1_000_000;
+            let base_fee = assembled.base_fee(
+                fee_multiplier,
+                (args.fee_ballast as u128).saturating_add(frac_ballast),
+                melvm::covenant_weight_from_bytes,
+            );
+            if let Some(limit) = args.max_weight {
+                if weight > limit {
+                    return Err(PrepareTxError::TooLarge { weight, limit });
+                }
+            }
+            if let Some(cap) = args.max_fee {
+                let needed = base_fee.max(actual_fee);
+                if needed > cap {
+                    return Err(PrepareTxError::FeeTooHigh { needed, cap });
+                }
+            }
+            if base_fee <= actual_fee {
+                return Ok(TxPreview {
+                    touched_coin_count: to_spend.len() - args.inputs.len(),
+                    inputs: to_spend,
+                    outputs: assembled.outputs.clone(),
+                    fee: assembled.fee,
+                    transaction: assembled,
+                });
+            }
+            if args.fee_override.is_some() {
+                // the explicit fee is too low for this transaction
+                return Err(PrepareTxError::InsufficientFunds {
+                    denom: FEE_DENOM,
+                    needed: base_fee,
+                    available: actual_fee,
+                });
             }
+            fee = base_fee;
         }
-        Err(PrepareTxError::InsufficientFunds(Denom::Mel))
     }
 
     /// Note a pending, outgoing transaction. This should be called *after* this transaction has been sent successfully to the network, and the main effect is to prevent the wallet from using the coins that the transaction spent, even before that transaction confirms.
-    pub fn add_pending(&mut self, tx: Transaction) {
-        self.pending_outgoing.insert(tx.hash_nosigs(), tx);
+    ///
+    /// Fails with [AddPendingError::Conflict] if the transaction spends a coin that another pending transaction already spends, since at most one of them can confirm. Use [Wallet::replace_pending] to deliberately replace a pending transaction. Adding a transaction that is already pending does nothing; use [Wallet::try_add_pending] to find out whether that happened.
+    pub fn add_pending(&mut self, tx: Transaction) -> Result<(), AddPendingError> {
+        self.try_add_pending(tx).map(|_| ())
+    }
+
+    /// Like [Wallet::add_pending], but returns whether the transaction was newly added, as opposed to already pending (with the same [Transaction::hash_nosigs]), in which case the wallet is left unchanged. This lets retry loops tell when they submitted the same transaction twice.
+    pub fn try_add_pending(&mut self, tx: Transaction) -> Result<bool, AddPendingError> {
+        let txhash = tx.hash_nosigs();
+        if self.pending_outgoing.contains_key(&txhash) {
+            return Ok(false);
+        }
+        if let Some(conflicting) = tx.inputs.iter().find_map(|coin| self.locked_by(coin)) {
+            return Err(AddPendingError::Conflict(conflicting));
+        }
+        self.pending_outgoing.insert(txhash, tx);
+        self.pending_heights.insert(txhash, self.height);
+        Ok(true)
+    }
+
+    /// Replaces the pending transaction `old` with `new`, which must spend all of the coins that `old` spends (and possibly more) while paying a strictly higher fee. This is how a stuck transaction is bumped: prepare a replacement spending the same coins with [PrepareTxArgs::fee_override], broadcast it, then record it here.
+    ///
+    /// On error, the wallet is left unchanged.
+    pub fn replace_pending(&mut self, old: TxHash, new: Transaction) -> Result<(), ReplaceError> {
+        let old_tx = self
+            .pending_outgoing
+            .get(&old)
+            .ok_or(ReplaceError::NotPending(old))?;
+        if let Some(missing) = old_tx.inputs.iter().find(|c| !new.inputs.contains(c)) {
+            return Err(ReplaceError::MissingInput(*missing));
+        }
+        if new.fee <= old_tx.fee {
+            return Err(ReplaceError::FeeNotHigher {
+                old: old_tx.fee,
+                new: new.fee,
+            });
+        }
+        let txhash = new.hash_nosigs();
+        if let Some((conflicting, _)) = self.pending_outgoing.iter().find(|(other, other_tx)| {
+            **other != old
+                && **other != txhash
+                && other_tx.inputs.iter().any(|c| new.inputs.contains(c))
+        }) {
+            return Err(ReplaceError::Conflict(*conflicting));
+        }
+        self.remove_pending(old);
+        self.pending_outgoing.insert(txhash, new);
+        self.pending_heights.insert(txhash, self.height);
+        Ok(())
+    }
+
+    /// Removes the pending transaction with the given hash, returning it if it was pending. Use this when a broadcast is known to have failed, to make the coins the transaction spent spendable again right away.
+    pub fn remove_pending(&mut self, txhash: TxHash) -> Option<Transaction> {
+        self.pending_heights.remove(&txhash);
+        self.pending_outgoing.remove(&txhash)
+    }
+
+    /// Removes the pending transactions none of whose inputs are confirmed coins of this wallet anymore, returning how many were removed. Such transactions have either confirmed or been beaten by a conflicting spend, so they lock nothing; this tidies up long-lived wallets, such as those persisted before [Wallet::add_coins] cleared such transactions by itself. Note that this also removes pending transactions that only spend coins from outside the wallet.
+    pub fn prune_pending(&mut self) -> usize {
+        let spent: Vec<TxHash> = self
+            .pending_outgoing
+            .iter()
+            .filter(|(_, tx)| {
+                tx.inputs
+                    .iter()
+                    .all(|coin| !self.confirmed_utxos.contains_key(coin))
+            })
+            .map(|(txhash, _)| *txhash)
+            .collect();
+        for txhash in spent.iter() {
+            self.remove_pending(*txhash);
+        }
+        spent.len()
+    }
+
+    /// Removes the pending transactions that were added more than `max_age` blocks before the current height, returning their hashes. Transactions that never confirm, such as those dropped from the mempool, would otherwise lock the coins they spend forever; evicting them makes those coins spendable again. The caller may rebroadcast the evicted transactions or prepare new ones.
+    ///
+    /// Pending transactions of unknown age, such as those loaded from wallets serialized before heights were tracked, count as added at height 0.
+    pub fn evict_expired_pending(&mut self, max_age: BlockHeight) -> Vec<TxHash> {
+        let expired: Vec<TxHash> = self
+            .pending_older_than(max_age)
+            .map(|(txhash, _)| *txhash)
+            .collect();
+        for txhash in expired.iter() {
+            self.pending_outgoing.remove(txhash);
+            self.pending_heights.remove(txhash);
+        }
+        expired
+    }
+
+    /// Lists the pending transactions that were added more than `min_age` blocks before the current height, and so are worth broadcasting again in case the network lost them. Like [Wallet::evict_expired_pending], this counts transactions of unknown age as added at height 0.
+    pub fn rebroadcast_candidates(&self, min_age: BlockHeight) -> Vec<&Transaction> {
+        self.pending_older_than(min_age).map(|(_, tx)| tx).collect()
+    }
+
+    /// Iterates over the pending transactions that were added more than `age` blocks before the current height.
+    fn pending_older_than(
+        &self,
+        age: BlockHeight,
+    ) -> impl Iterator<Item = (&TxHash, &Transaction)> + '_ {
+        let cutoff = BlockHeight(self.height.0.saturating_sub(age.0));
+        self.pending_outgoing.iter().filter(move |(txhash, _)| {
+            self.pending_heights
+                .get(txhash)
+                .copied()
+                .unwrap_or_default()
+                < cutoff
+        })
+    }
+
+    /// Notes that the given coin is locked by a covenant that only permits spending it from the given height onwards. Until then, [Wallet::prepare_tx] won't pick it.
+    ///
+    /// The note is dropped once the coin leaves the wallet for good: when it's spent in a block too deep to roll back, when it's rolled away by [Wallet::rollback_to], or when a [Wallet::full_reset] doesn't include it. The same goes for [Wallet::freeze].
+    pub fn set_maturity(&mut self, coin: CoinID, height: BlockHeight) {
+        self.maturity.insert(coin, height);
+    }
+
+    /// Sets the given coin aside, so that [Wallet::prepare_tx] won't pick it until it's unfrozen, for example to reserve it for a future staking operation. Frozen coins still count towards [Wallet::balances], but not [Wallet::spendable_balances].
+    pub fn freeze(&mut self, coin: CoinID) {
+        self.frozen.insert(coin);
+    }
+
+    /// Undoes [Wallet::freeze], letting [Wallet::prepare_tx] pick the given coin again.
+    pub fn unfreeze(&mut self, coin: CoinID) {
+        self.frozen.remove(&coin);
+    }
+
+    /// Lists the confirmed coins that can't be spent yet at the current height, with the height from which they can be.
+    pub fn immature_coins(&self) -> Vec<(CoinID, BlockHeight)> {
+        self.maturity
+            .iter()
+            .filter(|(coin, height)| {
+                **height > self.height && self.confirmed_utxos.contains_key(coin)
+            })
+            .map(|(coin, height)| (*coin, *height))
+            .collect()
+    }
+
+    /// Returns how many blocks have confirmed the given coin of this wallet: 1 if it was created in the block at the wallet's current height, 2 if in the block before, and so on. Returns `None` if the coin isn't one of this wallet's confirmed coins.
+    pub fn confirmations(&self, coin: &CoinID) -> Option<u64> {
+        let cdh = self.confirmed_utxos.get(coin)?;
+        Some(self.height.0.saturating_sub(cdh.height.0) + 1)
     }
 
     fn spendable_utxos(&self) -> impl Iterator<Item = (&CoinID, &CoinDataHeight)> + '_ {
-        self.confirmed_utxos.iter().filter(|(k, _)| {
-            // filter out the coins that a pending output is trying to spend
-            !self
-                .pending_outgoing
-                .iter()
-                .any(|(_, tx)| tx.inputs.iter().any(|pending_input| &pending_input == k))
+        let pending_inputs = self.pending_inputs();
+        self.confirmed_utxos
+            .iter()
+            .filter(move |(k, _)| self.is_spendable(k, &pending_inputs))
+    }
+
+    /// Whether the given confirmed coin can be spent, given the coins that pending transactions spend, as computed by [Wallet::pending_inputs].
+    fn is_spendable(&self, coin: &CoinID, pending_inputs: &BTreeSet<CoinID>) -> bool {
+        // filter out the coins that a pending output is trying to spend, as well as those frozen, reserved or not yet mature
+        !pending_inputs.contains(coin)
+            && !self.frozen.contains(coin)
+            && !self.reservations.contains(coin)
+            && self
+                .maturity
+                .get(coin)
+                .is_none_or(|height| *height <= self.height)
+    }
+
+    /// Returns the hash of the pending transaction that spends the given coin, if any. Coins spent by a pending transaction can't be spent again until that transaction confirms or is removed.
+    pub fn locked_by(&self, coin: &CoinID) -> Option<TxHash> {
+        self.pending_outgoing
+            .iter()
+            .find(|(_, tx)| tx.inputs.contains(coin))
+            .map(|(txhash, _)| *txhash)
+    }
+
+    /// Lists every coin that pending transactions spend, including coins from outside the wallet.
+    pub fn pending_inputs(&self) -> BTreeSet<CoinID> {
+        self.pending_outgoing
+            .values()
+            .flat_map(|tx| tx.inputs.iter().copied())
+            .collect()
+    }
+
+    /// Lists every output of the pending transactions, which are not confirmed yet, with the hash of its transaction and its index there. Outputs back to this wallet, such as change, become confirmed coins once their transaction confirms; see [Wallet::pending_balances].
+    pub fn pending_outputs(&self) -> Vec<(TxHash, usize, CoinData)> {
+        self.pending_outgoing
+            .iter()
+            .flat_map(|(txhash, tx)| {
+                tx.outputs
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, output)| (*txhash, i, output.clone()))
+            })
+            .collect()
+    }
+
+    /// Lists every confirmed coin that a pending transaction spends, with the hash of that transaction. This explains the difference between [Wallet::balances] and [Wallet::spendable_balances].
+    pub fn locked_coins(&self) -> BTreeMap<CoinID, TxHash> {
+        self.pending_outgoing
+            .iter()
+            .flat_map(|(txhash, tx)| tx.inputs.iter().map(move |coin| (*coin, *txhash)))
+            .filter(|(coin, _)| self.confirmed_utxos.contains_key(coin))
+            .collect()
+    }
+
+    /// Iterates over every confirmed coin of the wallet, with its status. A coin that is both frozen and being spent by a pending transaction counts as [CoinStatus::LockedByPending], since it's already on its way out, and a coin that is both frozen and reserved counts as [CoinStatus::Frozen].
+    pub fn all_coins(&self) -> impl Iterator<Item = (CoinID, CoinDataHeight, CoinStatus)> + '_ {
+        let locked = self.locked_coins();
+        self.confirmed_utxos.iter().map(move |(coin, cdh)| {
+            let status = match locked.get(coin) {
+                Some(txhash) => CoinStatus::LockedByPending(*txhash),
+                None if self.frozen.contains(coin) => CoinStatus::Frozen,
+                None => match self.reservations.reservation_of(coin) {
+                    Some(id) => CoinStatus::Reserved(id),
+                    None => CoinStatus::Confirmed,
+                },
+            };
+            (*coin, cdh.clone(), status)
         })
     }
+
+    fn is_tracked(&self, denom: Denom) -> bool {
+        self.tracked_denoms
+            .as_ref()
+            .is_none_or(|tracked| tracked.contains(&denom))
+    }
 }
 
 #[derive(Error, Debug, Serialize, Deserialize)]
 /// The error type returned by [crate::MelwalletdProtocol::prepare_tx].
 pub enum PrepareTxError<E: Error> {
-    #[error("not enough money (more of {0} needed)")]
-    InsufficientFunds(Denom),
+    #[error("not enough money ({needed} of {denom} needed, but only {available} available)")]
+    InsufficientFunds {
+        denom: Denom,
+        needed: CoinValue,
+        available: CoinValue,
+    },
 
     #[error("cannot spend external input coin {0}")]
     BadExternalInput(CoinID),
 
+    #[error("fee of {needed} exceeds the maximum of {cap}")]
+    FeeTooHigh { needed: CoinValue, cap: CoinValue },
+
+    #[error("output of {0} MEL is below the dust threshold")]
+    DustOutput(CoinValue),
+
+    #[error("output {0} has a value of zero")]
+    ZeroOutput(usize),
+
+    #[error("new fee of {new} is not higher than the old fee of {old}")]
+    FeeNotHigher { old: CoinValue, new: CoinValue },
+
+    #[error("fewer than two coins of {0} to consolidate")]
+    NothingToConsolidate(Denom),
+
+    #[error("signer cannot spend the coins of this wallet")]
+    SignerMismatch,
+
+    #[error("transaction weight of {weight} exceeds the limit of {limit}")]
+    TooLarge { weight: u128, limit: u128 },
+
+    #[error("fee factor of {0} is negative or not a number")]
+    BadFeeFactor(f64),
+
     #[error("signer refused to sign with error: {0}")]
     SignerRefused(#[from] E),
 }
 
-#[serde_as]
+impl<E: Error> PrepareTxError<E> {
+    /// Converts the signer error, if any, with the given function, leaving the other kinds of errors alone.
+    pub fn map_signer_error<F: Error>(self, f: impl FnOnce(E) -> F) -> PrepareTxError<F> {
+        match self {
+            PrepareTxError::InsufficientFunds {
+                denom,
+                needed,
+                available,
+            } => PrepareTxError::InsufficientFunds {
+                denom,
+                needed,
+                available,
+            },
+            PrepareTxError::BadExternalInput(coin) => PrepareTxError::BadExternalInput(coin),
+            PrepareTxError::FeeTooHigh { needed, cap } => {
+                PrepareTxError::FeeTooHigh { needed, cap }
+            }
+            PrepareTxError::DustOutput(value) => PrepareTxError::DustOutput(value),
+            PrepareTxError::ZeroOutput(index) => PrepareTxError::ZeroOutput(index),
+            PrepareTxError::FeeNotHigher { old, new } => PrepareTxError::FeeNotHigher { old, new },
+            PrepareTxError::NothingToConsolidate(denom) => {
+                PrepareTxError::NothingToConsolidate(denom)
+            }
+            PrepareTxError::SignerMismatch => PrepareTxError::SignerMismatch,
+            PrepareTxError::TooLarge { weight, limit } => {
+                PrepareTxError::TooLarge { weight, limit }
+            }
+            PrepareTxError::BadFeeFactor(factor) => PrepareTxError::BadFeeFactor(factor),
+            PrepareTxError::SignerRefused(e) => PrepareTxError::SignerRefused(f(e)),
+        }
+    }
+
+    /// Erases the type of the signer error, so that errors from different signers can be handled together, for example when mixing sync and async signers.
+    pub fn erase(self) -> PrepareTxError<ErasedSignerError>
+    where
+        E: Send + Sync + 'static,
+    {
+        self.map_signer_error(|e| ErasedSignerError(Box::new(e)))
+    }
+}
+
+/// A signer error of any type, as produced by [PrepareTxError::erase].
+#[derive(Error, Debug)]
+#[error(transparent)]
+pub struct ErasedSignerError(pub Box<dyn Error + Send + Sync>);
+
+/// The outcome of [Wallet::simulate_tx]: everything about a prepared transaction except its signatures.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TxPreview {
+    /// The inputs of the transaction, in order, with their contents. These include the external inputs from [PrepareTxArgs::inputs].
+    pub inputs: Vec<(CoinID, CoinDataHeight)>,
+    /// The outputs of the transaction, in order, including any change outputs.
+    pub outputs: Vec<CoinData>,
+    /// The fee the transaction pays.
+    pub fee: CoinValue,
+    /// How many of the wallet's own coins were picked as inputs.
+    pub touched_coin_count: usize,
+    /// The assembled transaction, with zeroed-out placeholder signatures.
+    pub transaction: Transaction,
+}
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 /// Constraints on what sort of transaction to prepare.
 pub struct PrepareTxArgs {
     /// "Kind" of the transaction.
     pub kind: TxKind,
-    /// **Additional** inputs of the transaction. Normally, this field can be left as an empty vector, in which case UTXOs locked by the wallet's own address are picked automatically.
+    /// **Additional** inputs of the transaction. Normally, this field can be left as an empty vector, in which case UTXOs locked by the wallet's own address are picked automatically. Coins already in the wallet (unless [PrepareTxArgs::coin_control] is on), as well as zero-valued coins, are rejected with [PrepareTxError::BadExternalInput].
     ///
     /// Use this field to specify "out of wallet" coins from dapps, multisig vaults, and such, which do not have their `covhash` field equal to the [Address] of the wallet, yet the wallet is able to spend, possibly in combination with other fields of [PrepareTxArgs]. For example, a multisig coin would not have the [Address] of any single-key wallet, and spending it must require explicitly specifying its [CoinID] and explicitly passing unlock arguments.
     ///
+    /// The wallet can't see whether these coins are still unspent on-chain, but it does reject, with [PrepareTxError::BadExternalInput], any that one of its own pending transactions already spends.
+    ///
     /// Optional in JSON, in which case it defaults to an empty list.
     #[serde(default)]
     pub inputs: Vec<(CoinID, CoinDataHeight)>,
-    /// **Required** outputs of the transaction. This generally specifies the "recipients" of the transaction. Note that this only specifies the first outputs of the transaction; more outputs may be created as "change" outputs.
+    /// **Required** outputs of the transaction. This generally specifies the "recipients" of the transaction. More outputs may be created as "change" outputs. For [TxKind::Normal] transactions, all outputs are then sorted by denomination, value, and address, so that the change output can't be told apart by its position; for other kinds, these outputs come first, in order.
+    ///
+    /// Coins of each denomination, such as [Denom::Sym], are picked to cover the outputs of that denomination, with change back in the same denomination. The fee is always paid in MEL, so sending any token takes some MEL too; without enough of either, preparing fails with [PrepareTxError::InsufficientFunds] for the denomination that's short.
+    ///
+    /// Outputs with [Denom::NewCustom] mint a new token; no inputs are selected for them. Use [minted_denom] on the prepared transaction to find out the denomination of the minted token.
+    ///
+    /// Outputs worth nothing are rejected with [PrepareTxError::ZeroOutput], except for [Denom::NewCustom] outputs.
     pub outputs: Vec<CoinData>,
     /// **Additional** covenants that must be included in the transaction. This is needed when spending out-of-wallet coins. Optional in JSON, defaulting to an empty list.
     #[serde(default)]
@@ -267,6 +1677,84 @@ pub struct PrepareTxArgs {
     #[serde(default)]
     /// Pretend like the transaction has this many more bytes when calculating the correct fee level. Useful in niche situations where you want to intentionally pay more fees than necessary.
     pub fee_ballast: usize,
+
+    #[serde(default)]
+    /// Pretend like the transaction is heavier by this fraction of its weight, in parts per million, when calculating the correct fee level, on top of [PrepareTxArgs::fee_ballast]. For example, 100_000 (that is, 10%) pays about 10% more fees, leaving headroom for signatures that turn out bigger than estimated. Capped at [MAX_FEE_BALLAST_PPM]. Fixed-point rather than a float, so that the arguments stay comparable with `Eq`. Optional in JSON, defaulting to zero.
+    pub fee_ballast_ppm: u32,
+
+    #[serde(default)]
+    /// How to pick the wallet's own coins to fund the transaction. Optional in JSON, defaulting to [CoinSelectionStrategy::ByCoinId].
+    pub coin_selection: CoinSelectionStrategy,
+
+    #[serde(default)]
+    /// Pay exactly this fee, instead of the minimum fee the transaction needs. Preparing the transaction fails with [PrepareTxError::InsufficientFunds] if the wallet can't afford it, and also if this is below the minimum fee, in which case the error reports the minimum fee as needed and this fee as available. Useful for bumping the fee of a stuck transaction.
+    pub fee_override: Option<CoinValue>,
+
+    #[serde(default)]
+    /// The most this transaction may pay in fees. Preparing the transaction fails with [PrepareTxError::FeeTooHigh] rather than paying more.
+    pub max_fee: Option<CoinValue>,
+
+    #[serde(default = "default_dust_threshold")]
+    /// MEL outputs worth less than this are "dust" that costs more in fees to spend than it's worth. Recipient outputs below this are rejected with [PrepareTxError::DustOutput], MEL change below this is paid as fees instead of being created, and MEL coins below this are only picked as inputs when the other coins aren't enough. Since other denominations can't pay fees, they are not affected. Optional in JSON, defaulting to [DEFAULT_DUST_THRESHOLD].
+    pub dust_threshold: CoinValue,
+
+    #[serde(default)]
+    /// Where to send change, instead of back to the wallet's own address. Change sent elsewhere no longer counts towards the wallet's balances. Optional in JSON, defaulting to the wallet's own address.
+    pub change_address: Option<Address>,
+
+    #[serde(default)]
+    /// Splits the change of each token into this many equal-ish outputs, so that later transactions have several coins to pick from. Fewer outputs are created if there isn't enough change to go around without creating MEL dust. Zero and one both mean a single change output per token. Optional in JSON, defaulting to zero.
+    pub split_change: usize,
+
+    #[serde(default)]
+    #[serde_as(as = "stdcode::HexBytes")]
+    /// The additional data of every change output, for protocols that tag wallet change. Recipient outputs are left alone. Optional and hex-encoded in JSON, defaulting to an empty string.
+    pub change_data: Bytes,
+
+    #[serde(default)]
+    /// Puts the change of each token in a single output of its own, after all the recipient outputs, instead of shuffling it in with them, for callers such as swap dapps that need to pick the change out of the transaction. With MEL change only, the change is simply the last output. This overrides [PrepareTxArgs::split_change], and MEL change below the dust threshold is still paid as fees. Optional in JSON, defaulting to false.
+    pub change_isolation: bool,
+
+    #[serde(default)]
+    /// Marks the transaction as not meant to confirm before this height, by prefixing its data as described in [encode_locktime]. Mel has no native locktime, so this is only enforced by covenants that check it. Optional in JSON, defaulting to no locktime.
+    pub locktime: Option<BlockHeight>,
+
+    #[serde(default)]
+    /// Makes [Wallet::prepare_tx], as well as its async and parallel variants, check that the signer can actually spend the coins of this wallet, failing with [PrepareTxError::SignerMismatch] otherwise. See [Wallet::matches_signer]. Optional in JSON, defaulting to no check.
+    pub check_signer: bool,
+
+    #[serde(default)]
+    #[serde_as(as = "Vec<(Same, Same)>")]
+    /// Unlock arguments for inputs that aren't unlocked by a signature, such as external coins locked by a hashlock, by [CoinID]. Each goes directly into the signature slot of its input, and the signer is not asked to sign that input. Optional in JSON, defaulting to none.
+    pub input_unlock_args: BTreeMap<CoinID, Bytes>,
+
+    #[serde(default)]
+    /// Spends exactly the coins in [PrepareTxArgs::inputs] and never picks any others, failing with [PrepareTxError::InsufficientFunds] if they don't cover the outputs and the fee. With this on, `inputs` may also list spendable coins of this wallet. Optional in JSON, defaulting to off.
+    pub coin_control: bool,
+
+    #[serde(default)]
+    /// How many confirmations, as counted by [Wallet::confirmations], a coin of the wallet needs before it's picked, so that coins that a reorg might undo aren't spent. Optional in JSON, defaulting to 0, so that every coin can be picked.
+    pub min_confirmations: BlockHeight,
+
+    #[serde(default)]
+    /// The largest weight, as computed by [Transaction::weight], that the prepared transaction may have. Preparing a heavier transaction fails with [PrepareTxError::TooLarge]. Outputs and data make transactions heavier, while inputs make them lighter, since spending coins shrinks the set of coins the network keeps. The limit is checked on each assembled transaction rather than during coin selection, since weight doesn't simply shrink as coins are picked: the first coin from this wallet also brings in the signer's covenant, which may well weigh more than an input saves. Still, it's mostly many outputs or large data that run into the limit, not many inputs. Optional in JSON, defaulting to no limit.
+    pub max_weight: Option<u128>,
+}
+
+/// The denomination that transaction fees are paid in. Mel only accepts fees in MEL, so this is [Denom::Mel]; all of the fee handling of this crate goes through this constant.
+pub const FEE_DENOM: Denom = Denom::Mel;
+
+/// The default [PrepareTxArgs::dust_threshold]: 0.001 MEL, roughly what an extra output costs in fees at the base fee multiplier.
+pub const DEFAULT_DUST_THRESHOLD: CoinValue = CoinValue(1000);
+
+/// The [PrepareTxArgs::max_weight] of the transactions [Wallet::prepare_split] prepares, as computed by [Transaction::weight]. Each output weighs a bit over 1000, so this allows splits into several hundred coins, while keeping the transaction, and its fee, within reason.
+pub const MAX_SPLIT_WEIGHT: u128 = 1_000_000;
+
+/// The largest [PrepareTxArgs::fee_ballast_ppm] that counts: a million parts per million, doubling the fee. Anything above is treated as this, so that a typo can't pay many times the fee.
+pub const MAX_FEE_BALLAST_PPM: u32 = 1_000_000;
+
+fn default_dust_threshold() -> CoinValue {
+    DEFAULT_DUST_THRESHOLD
 }
 
 impl Default for PrepareTxArgs {
@@ -278,6 +1766,105 @@ impl Default for PrepareTxArgs {
             covenants: vec![],
             data: Default::default(),
             fee_ballast: 0,
+            fee_ballast_ppm: 0,
+            coin_selection: CoinSelectionStrategy::default(),
+            fee_override: None,
+            max_fee: None,
+            dust_threshold: DEFAULT_DUST_THRESHOLD,
+            change_address: None,
+            split_change: 0,
+            change_data: Bytes::new(),
+            change_isolation: false,
+            locktime: None,
+            check_signer: false,
+            input_unlock_args: BTreeMap::new(),
+            coin_control: false,
+            min_confirmations: BlockHeight(0),
+            max_weight: None,
         }
     }
 }
+
+/// Sums up the values of the given coins, by token, saturating like [saturating_sum].
+fn tally<'a>(coins: impl Iterator<Item = &'a CoinData>) -> BTreeMap<Denom, CoinValue> {
+    coins.fold(BTreeMap::new(), |mut map, coin_data| {
+        add_saturating(map.entry(coin_data.denom).or_default(), coin_data.value);
+        map
+    })
+}
+
+/// Sums up coin values, saturating at `u128::MAX` instead of overflowing. Real coins are worth at most [melstructs::MAX_COINVAL], so sums only saturate for coins the network would never produce, such as those in maliciously crafted coin diffs.
+pub(crate) fn saturating_sum(values: impl IntoIterator<Item = CoinValue>) -> CoinValue {
+    values.into_iter().fold(CoinValue(0), |mut total, value| {
+        add_saturating(&mut total, value);
+        total
+    })
+}
+
+/// Adds `value` to `total`, saturating at `u128::MAX`.
+pub(crate) fn add_saturating(total: &mut CoinValue, value: CoinValue) {
+    total.0 = total.0.saturating_add(value.0);
+}
+
+/// Converts a coin value into a signed balance change, saturating at `i128::MAX`.
+pub(crate) fn signed_value(value: CoinValue) -> i128 {
+    i128::try_from(value.0).unwrap_or(i128::MAX)
+}
+
+/// Replaces the placeholder signatures of an assembled transaction with real signatures for every input, except those with unlock arguments, which get their unlock arguments instead. Inputs whose coins are in `spent` are signed with [Signer::sign_detached_with_context].
+fn sign_all<S: Signer>(
+    mut assembled: Transaction,
+    signer: &S,
+    unlock_args: &BTreeMap<CoinID, Bytes>,
+    spent: &BTreeMap<CoinID, CoinDataHeight>,
+) -> Result<Transaction, S::Error> {
+    let sigs = assembled
+        .inputs
+        .iter()
+        .enumerate()
+        .map(
+            |(i, coin_id)| match (unlock_args.get(coin_id), spent.get(coin_id)) {
+                (Some(unlock_arg), _) => Ok(unlock_arg.clone()),
+                (None, Some(cdh)) => signer.sign_detached_with_context(&assembled, i, cdh),
+                (None, None) => signer.sign_detached(&assembled, i),
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+    assembled.sigs = sigs;
+    Ok(assembled)
+}
+
+/// Whether an output of a transaction of the given kind has to be paid for with inputs, unlike newly minted tokens, or the ERG minted by a [TxKind::DoscMint] transaction.
+fn needs_inputs(kind: TxKind, output: &CoinData) -> bool {
+    output.denom != Denom::NewCustom && !(kind == TxKind::DoscMint && output.denom == Denom::Erg)
+}
+
+/// Returns the denomination of the new token minted by the given transaction, if any of its outputs has [Denom::NewCustom]. The network derives the new denomination from the hash of the transaction, so it can only be known once the transaction is fully assembled.
+pub fn minted_denom(tx: &Transaction) -> Option<Denom> {
+    if tx.outputs.iter().any(|out| out.denom == Denom::NewCustom) {
+        Some(Denom::Custom(tx.hash_nosigs()))
+    } else {
+        None
+    }
+}
+
+/// The tag that starts the data of a transaction with a locktime.
+pub const LOCKTIME_TAG: &[u8; 8] = b"locktime";
+
+/// Prefixes transaction data with a locktime: [LOCKTIME_TAG], then the height as a big-endian `u64`, then the original data.
+///
+/// Mel transactions have no native locktime, so this is only a convention. It takes effect when the coins being spent have covenants that check it: such a covenant reads the height out of bytes 8 to 16 of the spending transaction's data, and compares it against the height of the last block header.
+pub fn encode_locktime(height: BlockHeight, data: &[u8]) -> Bytes {
+    let mut encoded = Vec::with_capacity(16 + data.len());
+    encoded.extend_from_slice(LOCKTIME_TAG);
+    encoded.extend_from_slice(&height.0.to_be_bytes());
+    encoded.extend_from_slice(data);
+    encoded.into()
+}
+
+/// Returns the locktime of the given transaction, if its data starts with one as encoded by [encode_locktime].
+pub fn locktime_of(tx: &Transaction) -> Option<BlockHeight> {
+    let rest = tx.data.strip_prefix(LOCKTIME_TAG.as_slice())?;
+    let height = rest.get(..8)?.try_into().ok()?;
+    Some(BlockHeight(u64::from_be_bytes(height)))
+}
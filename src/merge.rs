@@ -0,0 +1,90 @@
+use melstructs::{BlockHeight, TxHash};
+use thiserror::Error;
+
+use crate::Wallet;
+
+#[derive(Error, Debug)]
+pub enum MergeError {
+    #[error("wallets are on different networks")]
+    WrongNetwork,
+
+    #[error("wallets have different addresses")]
+    WrongAddress,
+
+    #[error("wallets are at different heights ({ours} and {theirs})")]
+    DifferentHeight {
+        ours: BlockHeight,
+        theirs: BlockHeight,
+    },
+
+    #[error(
+        "pending transaction {theirs} spends coins already spent by pending transaction {ours}"
+    )]
+    Conflict { ours: TxHash, theirs: TxHash },
+}
+
+impl Wallet {
+    /// Merges another wallet for the same address, synced to the same height, into this one, such as a shard that only tracks some denominations (see [Wallet::tracked_denoms]), or a copy that prepared transactions of its own. The merged wallet has every confirmed coin of either, and every pending transaction of either, as well as their frozen coins and maturities, and tracks the denominations either tracked.
+    ///
+    /// Shards synced over different ranges of heights can't be merged: a later shard never held the coins that an earlier one received, so it has no record of spending them, and the merged wallet would wrongly keep them.
+    ///
+    /// Fails if the wallets are for different networks, addresses or heights, or if a pending transaction of `other` spends coins that a different pending transaction of this wallet already spends, since at most one of them can confirm. On error, the wallet is left unchanged.
+    ///
+    /// The undo information of both wallets is dropped, since it no longer describes the merged coins, so blocks before the merge can't be rolled back. The history of `other` isn't merged either.
+    pub fn merge(&mut self, other: &Wallet) -> Result<(), MergeError> {
+        if other.netid != self.netid {
+            return Err(MergeError::WrongNetwork);
+        }
+        if other.address != self.address {
+            return Err(MergeError::WrongAddress);
+        }
+        if other.height != self.height {
+            return Err(MergeError::DifferentHeight {
+                ours: self.height,
+                theirs: other.height,
+            });
+        }
+        for (txhash, tx) in other.pending_outgoing.iter() {
+            if let Some(ours) = tx
+                .inputs
+                .iter()
+                .find_map(|coin| self.locked_by(coin).filter(|locker| locker != txhash))
+            {
+                return Err(MergeError::Conflict {
+                    ours,
+                    theirs: *txhash,
+                });
+            }
+        }
+
+        self.confirmed_utxos
+            .extend(other.confirmed_utxos.iter().map(|(k, v)| (*k, v.clone())));
+        for (txhash, tx) in other.pending_outgoing.iter() {
+            if !self.pending_outgoing.contains_key(txhash) {
+                self.pending_outgoing.insert(*txhash, tx.clone());
+                if let Some(height) = other.pending_heights.get(txhash) {
+                    self.pending_heights.insert(*txhash, *height);
+                }
+            }
+        }
+        self.maturity
+            .extend(other.maturity.iter().map(|(k, v)| (*k, *v)));
+        self.frozen.extend(other.frozen.iter().copied());
+        // the merged wallet keeps track of everything either shard did
+        self.tracked_denoms = match (self.tracked_denoms.take(), &other.tracked_denoms) {
+            (Some(mut ours), Some(theirs)) => {
+                ours.extend(theirs.iter().copied());
+                Some(ours)
+            }
+            _ => None,
+        };
+        self.recent_blocks.clear();
+        // with no undo information left, spent coins can't come back
+        let confirmed_utxos = &self.confirmed_utxos;
+        self.maturity
+            .retain(|coin, _| confirmed_utxos.contains_key(coin));
+        self.frozen
+            .retain(|coin| confirmed_utxos.contains_key(coin));
+        Ok(())
+    }
+}
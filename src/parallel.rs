@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+use melstructs::{CoinDataHeight, CoinID, Transaction};
+use rayon::prelude::*;
+
+use crate::{PrepareTxArgs, PrepareTxError, Signer, Wallet};
+
+impl Wallet {
+    /// Like [Wallet::prepare_tx], but signs all the inputs in parallel, which speeds up transactions with hundreds of inputs, such as large consolidations. The signatures are the same as those [Wallet::prepare_tx] produces, in the same order.
+    pub fn prepare_tx_parallel<S: Signer + Sync>(
+        &self,
+        args: PrepareTxArgs,
+        signer: &S,
+        fee_multiplier: u128,
+    ) -> Result<Transaction, PrepareTxError<S::Error>>
+    where
+        S::Error: Send,
+    {
+        if args.check_signer && !self.matches_signer(signer) {
+            return Err(PrepareTxError::SignerMismatch);
+        }
+        let preview =
+            self.assemble_tx(&args, signer.covenant(), signer.sig_size(), fee_multiplier)?;
+        let spent = preview.inputs.into_iter().collect();
+        Ok(sign_all_parallel(
+            preview.transaction,
+            signer,
+            &args.input_unlock_args,
+            &spent,
+        )?)
+    }
+}
+
+/// Like [crate::sign_all], but produces the signatures in parallel.
+fn sign_all_parallel<S: Signer + Sync>(
+    mut assembled: Transaction,
+    signer: &S,
+    unlock_args: &BTreeMap<CoinID, Bytes>,
+    spent: &BTreeMap<CoinID, CoinDataHeight>,
+) -> Result<Transaction, S::Error>
+where
+    S::Error: Send,
+{
+    let sigs = assembled
+        .inputs
+        .par_iter()
+        .enumerate()
+        .map(
+            |(i, coin_id)| match (unlock_args.get(coin_id), spent.get(coin_id)) {
+                (Some(unlock_arg), _) => Ok(unlock_arg.clone()),
+                (None, Some(cdh)) => signer.sign_detached_with_context(&assembled, i, cdh),
+                (None, None) => signer.sign_detached(&assembled, i),
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+    assembled.sigs = sigs;
+    Ok(assembled)
+}
@@ -0,0 +1,104 @@
+use std::convert::Infallible;
+
+use bytes::Bytes;
+use melstructs::{CoinDataHeight, Transaction};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{PrepareTxArgs, PrepareTxError, Signer, Wallet};
+
+/// A transaction that may still be missing some signatures, along with everything a signer needs to know about it. This can be serialized and passed between machines, so that inputs can be signed on air-gapped machines or by several parties, none of which needs a [Wallet].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PartialTransaction {
+    /// The transaction. The signatures of unsigned inputs are zeroed-out placeholders.
+    pub transaction: Transaction,
+    /// The coin spent by each input of the transaction, in order, so that signers can see what they are signing.
+    pub input_coins: Vec<CoinDataHeight>,
+    /// Whether each input of the transaction is signed yet.
+    pub signed: Vec<bool>,
+}
+
+#[derive(Error, Debug)]
+pub enum PartialError {
+    #[error("input {0} is not signed")]
+    Unsigned(usize),
+}
+
+impl Wallet {
+    /// Prepares a transaction like [Wallet::prepare_unsigned], but exports it as a [PartialTransaction] to be signed elsewhere with [apply_signer]. Inputs with [PrepareTxArgs::input_unlock_args] already count as signed.
+    pub fn export_partial(
+        &self,
+        args: &PrepareTxArgs,
+        sig_size: usize,
+        covenant: Bytes,
+        fee_multiplier: u128,
+    ) -> Result<PartialTransaction, PrepareTxError<Infallible>> {
+        let preview = self.simulate_tx(args, sig_size, covenant, fee_multiplier)?;
+        Ok(PartialTransaction {
+            signed: preview
+                .inputs
+                .iter()
+                .map(|(coin_id, _)| args.input_unlock_args.contains_key(coin_id))
+                .collect(),
+            input_coins: preview.inputs.into_iter().map(|(_, cdh)| cdh).collect(),
+            transaction: preview.transaction,
+        })
+    }
+
+    /// Prepares a transaction like [Wallet::export_partial], and encodes it as JSON for external signing tools, which may be written in other languages. Parse it back with [PartialTransaction::from_json].
+    ///
+    /// The JSON is an object with three fields:
+    /// - `transaction`: the transaction, as `melstructs` serializes it. Its `kind`, and the `covhash` (in address form) and `denom` of each output, are strings; `inputs` are objects with a `txhash` (hex) and an `index`; values and the `fee` are integers; and the `covenants`, `data`, `sigs`, and the `additional_data` of each output are hex strings, as with `stdcode::HexBytes`.
+    /// - `input_coins`: the coin spent by each input, in order, as objects with a `coin_data` (shaped like an output) and a `height`. This is the context signers need to check what they sign.
+    /// - `signed`: whether each input is signed yet. Unsigned inputs have zeroed-out placeholder signatures in `transaction.sigs`.
+    pub fn unsigned_tx_json(
+        &self,
+        args: &PrepareTxArgs,
+        sig_size: usize,
+        covenant: Bytes,
+        fee_multiplier: u128,
+    ) -> Result<String, PrepareTxError<Infallible>> {
+        Ok(self
+            .export_partial(args, sig_size, covenant, fee_multiplier)?
+            .to_json())
+    }
+}
+
+impl PartialTransaction {
+    /// Parses a partial transaction from the JSON produced by [Wallet::unsigned_tx_json] or [PartialTransaction::to_json].
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Encodes the partial transaction as JSON, in the schema described at [Wallet::unsigned_tx_json].
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+/// Signs every unsigned input of the partial transaction that spends a coin locked by the signer's covenant, leaving the other inputs alone.
+pub fn apply_signer<S: Signer>(
+    mut partial: PartialTransaction,
+    signer: &S,
+) -> Result<PartialTransaction, S::Error> {
+    let address = tmelcrypt::hash_single(signer.covenant()).into();
+    for (i, cdh) in partial.input_coins.iter().enumerate() {
+        if cdh.coin_data.covhash == address
+            && partial.signed.get(i) == Some(&false)
+            && i < partial.transaction.sigs.len()
+        {
+            partial.transaction.sigs[i] =
+                signer.sign_detached_with_context(&partial.transaction, i, cdh)?;
+            partial.signed[i] = true;
+        }
+    }
+    Ok(partial)
+}
+
+/// Extracts the fully signed transaction out of a partial transaction. Fails if any input is still unsigned.
+pub fn finalize(partial: PartialTransaction) -> Result<Transaction, PartialError> {
+    if let Some(i) = partial.signed.iter().position(|signed| !signed) {
+        return Err(PartialError::Unsigned(i));
+    }
+    Ok(partial.transaction)
+}
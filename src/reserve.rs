@@ -0,0 +1,64 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use melstructs::CoinID;
+use serde::{Deserialize, Serialize};
+
+use crate::Wallet;
+
+/// Identifies a set of coins reserved with [Wallet::reserve].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ReservationId(pub u64);
+
+/// The coins of a [Wallet] that are currently reserved, by reservation. See [Wallet::reserve].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reservations {
+    next_id: u64,
+    by_id: BTreeMap<ReservationId, BTreeSet<CoinID>>,
+}
+
+impl Reservations {
+    /// Whether the given coin is reserved.
+    pub fn contains(&self, coin: &CoinID) -> bool {
+        self.by_id.values().any(|coins| coins.contains(coin))
+    }
+
+    /// Returns the reservation holding the given coin, if any.
+    pub fn reservation_of(&self, coin: &CoinID) -> Option<ReservationId> {
+        self.by_id
+            .iter()
+            .find(|(_, coins)| coins.contains(coin))
+            .map(|(id, _)| *id)
+    }
+
+    /// Whether no coins are reserved.
+    pub fn is_empty(&self) -> bool {
+        self.by_id.is_empty()
+    }
+
+    /// Drops the given coin from its reservation, if any, along with the reservation if that leaves it empty.
+    pub(crate) fn forget(&mut self, coin: &CoinID) {
+        self.by_id.retain(|_, coins| {
+            coins.remove(coin);
+            !coins.is_empty()
+        });
+    }
+}
+
+impl Wallet {
+    /// Reserves the given coins, so that [Wallet::prepare_tx] won't pick them until the returned reservation is released with [Wallet::release], for dapps that build several transactions in a row and don't want a payment made in between to take the coins they've planned on. Unlike [Wallet::freeze], reservations are transient: they are never serialized, so they don't survive saving and loading the wallet. Reserved coins that get spent, or leave the wallet for good, are dropped from their reservation, and a reservation left with no coins is gone, as if released.
+    ///
+    /// To spend reserved coins, release them and prepare the transaction with [crate::PrepareTxArgs::coin_control], without giving up the mutable borrow of the wallet in between.
+    pub fn reserve(&mut self, coins: &[CoinID]) -> ReservationId {
+        let id = ReservationId(self.reservations.next_id);
+        self.reservations.next_id += 1;
+        self.reservations
+            .by_id
+            .insert(id, coins.iter().copied().collect());
+        id
+    }
+
+    /// Releases the coins of the given reservation. Releasing a reservation twice does nothing.
+    pub fn release(&mut self, id: ReservationId) {
+        self.reservations.by_id.remove(&id);
+    }
+}
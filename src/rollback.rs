@@ -0,0 +1,93 @@
+use melstructs::{BlockHeight, CoinDataHeight, CoinID, Transaction};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::Wallet;
+
+/// How many of the most recent blocks a [Wallet] remembers enough about to undo with [Wallet::rollback_to]. Reorgs deeper than this require a [Wallet::full_reset].
+pub const MAX_ROLLBACK_DEPTH: usize = 100;
+
+/// Everything needed to undo the effect of one block on a [Wallet].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockUndo {
+    /// The wallet height before this block was applied.
+    pub prev_height: BlockHeight,
+    /// The height of this block.
+    pub height: BlockHeight,
+    /// Coins this block added to the wallet.
+    pub added: Vec<CoinID>,
+    /// Coins this block removed from the wallet, with their contents.
+    pub removed: Vec<(CoinID, CoinDataHeight)>,
+    /// Pending transactions this block confirmed.
+    pub confirmed_pending: Vec<Transaction>,
+}
+
+#[derive(Error, Debug)]
+pub enum RollbackError {
+    #[error("cannot roll back to a height above the current height")]
+    BadHeight,
+
+    #[error("cannot roll back more than {MAX_ROLLBACK_DEPTH} blocks, or past a full reset")]
+    TooDeep,
+
+    #[error("cannot roll back to a height inside a range of blocks applied together, from {from} to {to}")]
+    InsideRange { from: BlockHeight, to: BlockHeight },
+}
+
+impl Wallet {
+    /// Undoes every block above the given height, restoring the coins they spent and removing the coins they created. Pending transactions that those blocks confirmed become pending again. Use this when the network reorganizes, then apply the new blocks with [Wallet::add_coins] as usual.
+    ///
+    /// Only the last [MAX_ROLLBACK_DEPTH] blocks, since the last [Wallet::full_reset], can be rolled back. Blocks applied together by [Wallet::add_coins_range] can only be rolled back as a whole, so rolling back to a height inside such a range fails with [RollbackError::InsideRange]. On error, the wallet is left unchanged.
+    pub fn rollback_to(&mut self, height: BlockHeight) -> Result<(), RollbackError> {
+        if height > self.height {
+            return Err(RollbackError::BadHeight);
+        }
+        if height == self.height {
+            return Ok(());
+        }
+        // make sure we remember every block down to the target height before touching anything
+        let earliest = self
+            .recent_blocks
+            .iter()
+            .rev()
+            .take_while(|undo| undo.height > height)
+            .last()
+            .ok_or(RollbackError::TooDeep)?;
+        if earliest.prev_height > height {
+            return Err(RollbackError::TooDeep);
+        }
+        if earliest.prev_height < height {
+            return Err(RollbackError::InsideRange {
+                from: earliest.prev_height + BlockHeight(1),
+                to: earliest.height,
+            });
+        }
+
+        // pending transactions that become pending again count as added at the new height
+        while let Some(undo) = self.recent_blocks.pop_back() {
+            if undo.height <= height {
+                self.recent_blocks.push_back(undo);
+                break;
+            }
+            for coin in undo.added.iter() {
+                self.confirmed_utxos.remove(coin);
+            }
+            self.forget_coins(undo.added.iter());
+            self.confirmed_utxos.extend(undo.removed);
+            for tx in undo.confirmed_pending {
+                let txhash = tx.hash_nosigs();
+                self.pending_outgoing.insert(txhash, tx);
+                self.pending_heights.insert(txhash, height);
+            }
+        }
+        while self
+            .history
+            .last()
+            .is_some_and(|entry| entry.height > height)
+        {
+            self.history.pop();
+        }
+        self.height = height;
+        Ok(())
+    }
+}
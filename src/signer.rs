@@ -1,8 +1,9 @@
-use std::convert::Infallible;
+use std::{convert::Infallible, future::Future, sync::OnceLock};
 
 use bytes::Bytes;
-use melstructs::Transaction;
-use tmelcrypt::Ed25519SK;
+use melstructs::{CoinDataHeight, Transaction};
+use melvm::opcode::OpCode;
+use tmelcrypt::{Ed25519PK, Ed25519SK};
 
 /// Represents something that can sign transactions.
 pub trait Signer {
@@ -16,9 +17,104 @@ pub trait Signer {
 
     /// Signs a transaction. May return an error if the signer refuses to sign the transaction for whatever reason.
     fn sign(&self, txn: &Transaction, for_input: usize) -> Result<Transaction, Self::Error>;
+
+    /// Produces just the signature that [Signer::sign] would put in the `for_input`-th entry of the transaction's signatures, without touching the transaction. This avoids cloning the transaction per input, and lets several signers sign the same transaction concurrently.
+    fn sign_detached(&self, txn: &Transaction, for_input: usize) -> Result<Bytes, Self::Error> {
+        let signed = self.sign(txn, for_input)?;
+        Ok(signed.sigs.get(for_input).cloned().unwrap_or_default())
+    }
+
+    /// Like [Signer::sign], but also given the coin that the `for_input`-th input spends, so that signers such as hardware wallets can show its value and denomination before signing. Defaults to ignoring the coin and calling [Signer::sign].
+    fn sign_with_context(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+        spent: &CoinDataHeight,
+    ) -> Result<Transaction, Self::Error> {
+        let _ = spent;
+        self.sign(txn, for_input)
+    }
+
+    /// The detached counterpart of [Signer::sign_with_context], in the same way that [Signer::sign_detached] is that of [Signer::sign]. This is what [crate::Wallet::prepare_tx] calls for every input it signs, so signers that show the spent coin should override it too. Defaults to ignoring the coin and calling [Signer::sign_detached].
+    fn sign_detached_with_context(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+        spent: &CoinDataHeight,
+    ) -> Result<Bytes, Self::Error> {
+        let _ = spent;
+        self.sign_detached(txn, for_input)
+    }
+
+    /// Returns the public key of this signer, if it has a single one, so that callers can show which key controls a wallet. Defaults to `None`.
+    fn public_identity(&self) -> Option<Bytes> {
+        None
+    }
+}
+
+/// Represents something that can sign transactions asynchronously, such as a remote signing service or a hardware wallet that waits for the user to confirm. Every [Signer] is also an [AsyncSigner].
+///
+/// The methods are prefixed with `async_` so that they don't clash with those of [Signer] when both traits are in scope.
+pub trait AsyncSigner {
+    type Error: std::error::Error;
+
+    /// Returns the raw, unhashed covenant that returns true given transactions spent by this signer.
+    fn async_covenant(&self) -> Bytes;
+
+    /// Returns a conservative estimate of the signature size.
+    fn async_sig_size(&self) -> usize;
+
+    /// Signs a transaction. May return an error if the signer refuses to sign the transaction for whatever reason.
+    fn async_sign(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+    ) -> impl Future<Output = Result<Transaction, Self::Error>>;
+
+    /// Like [AsyncSigner::async_sign], but also given the coin that the `for_input`-th input spends, like [Signer::sign_with_context], so that hardware wallets can show its value and denomination before the user confirms. Defaults to ignoring the coin and calling [AsyncSigner::async_sign].
+    fn async_sign_with_context(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+        spent: &CoinDataHeight,
+    ) -> impl Future<Output = Result<Transaction, Self::Error>> {
+        let _ = spent;
+        self.async_sign(txn, for_input)
+    }
+}
+
+impl<S: Signer> AsyncSigner for S {
+    type Error = S::Error;
+
+    fn async_covenant(&self) -> Bytes {
+        self.covenant()
+    }
+
+    fn async_sig_size(&self) -> usize {
+        self.sig_size()
+    }
+
+    fn async_sign(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+    ) -> impl Future<Output = Result<Transaction, Self::Error>> {
+        std::future::ready(self.sign(txn, for_input))
+    }
+
+    fn async_sign_with_context(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+        spent: &CoinDataHeight,
+    ) -> impl Future<Output = Result<Transaction, Self::Error>> {
+        std::future::ready(self.sign_with_context(txn, for_input, spent))
+    }
 }
 
 /// An ed25519-based signer.
+///
+/// MelVM can only check ed25519 signatures (with `SigEOk`), so ed25519 is the only signature scheme a covenant can verify natively; there is no secp256k1 counterpart to this signer.
 pub struct StdEd25519Signer(pub Ed25519SK);
 
 impl Signer for StdEd25519Signer {
@@ -38,7 +134,184 @@ impl Signer for StdEd25519Signer {
         // make sure sigs is big enough, padding it with empty elements as needed
         txn.sigs
             .resize((for_input + 1).max(txn.sigs.len()), Bytes::new());
-        txn.sigs[for_input] = self.0.sign(&txn.hash_nosigs().0).into();
+        txn.sigs[for_input] = self.sign_detached(&txn, for_input)?;
+        Ok(txn)
+    }
+
+    fn sign_detached(&self, txn: &Transaction, _for_input: usize) -> Result<Bytes, Self::Error> {
+        Ok(self.0.sign(&txn.hash_nosigs().0).into())
+    }
+
+    /// the ed25519 public key
+    fn public_identity(&self) -> Option<Bytes> {
+        Some(self.0.to_public().0.to_vec().into())
+    }
+}
+
+/// A signer that remembers the covenant and signature size of another signer after first asking for them, for signers where computing those is expensive, such as remote signers. Signing is passed through as-is.
+pub struct CachingSigner<S> {
+    inner: S,
+    covenant: OnceLock<Bytes>,
+    sig_size: OnceLock<usize>,
+}
+
+impl<S: Signer> CachingSigner<S> {
+    /// Wraps the given signer.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            covenant: OnceLock::new(),
+            sig_size: OnceLock::new(),
+        }
+    }
+
+    /// Returns the wrapped signer.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Signer> Signer for CachingSigner<S> {
+    type Error = S::Error;
+
+    fn covenant(&self) -> Bytes {
+        self.covenant.get_or_init(|| self.inner.covenant()).clone()
+    }
+
+    fn sig_size(&self) -> usize {
+        *self.sig_size.get_or_init(|| self.inner.sig_size())
+    }
+
+    fn sign(&self, txn: &Transaction, for_input: usize) -> Result<Transaction, Self::Error> {
+        self.inner.sign(txn, for_input)
+    }
+
+    fn sign_detached(&self, txn: &Transaction, for_input: usize) -> Result<Bytes, Self::Error> {
+        self.inner.sign_detached(txn, for_input)
+    }
+
+    fn sign_with_context(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+        spent: &CoinDataHeight,
+    ) -> Result<Transaction, Self::Error> {
+        self.inner.sign_with_context(txn, for_input, spent)
+    }
+
+    fn sign_detached_with_context(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+        spent: &CoinDataHeight,
+    ) -> Result<Bytes, Self::Error> {
+        self.inner.sign_detached_with_context(txn, for_input, spent)
+    }
+
+    fn public_identity(&self) -> Option<Bytes> {
+        self.inner.public_identity()
+    }
+}
+
+/// A signer for coins locked by an n-of-n multisig covenant, combining several member signers.
+///
+/// The signature for each input is the concatenation of every member's signature, in order, with each member's signature zero-padded to that member's [Signer::sig_size]. The covenant is responsible for checking each member's signature at its offset; [MultiSigner::std_ed25519] builds such a covenant for ed25519 keys.
+///
+/// A [MultiSigner] works with [crate::Wallet::prepare_tx] like any other signer: the wallet's address should be the hash of [MultiSigner::covenant], and every member signs every input.
+pub struct MultiSigner<E> {
+    covenant: Bytes,
+    signers: Vec<Box<dyn Signer<Error = E>>>,
+}
+
+impl<E> MultiSigner<E> {
+    /// Creates a multisig signer from the combined covenant and its member signers, in the order the covenant expects their signatures.
+    pub fn new(covenant: Bytes, signers: Vec<Box<dyn Signer<Error = E>>>) -> Self {
+        Self { covenant, signers }
+    }
+}
+
+impl MultiSigner<Infallible> {
+    /// Creates an n-of-n multisig signer from ed25519 secret keys, with a covenant that requires a valid signature from every key.
+    pub fn std_ed25519(keys: Vec<Ed25519SK>) -> Self {
+        let mut ops = vec![];
+        for (i, key) in keys.iter().enumerate() {
+            ops.extend(ed25519_slot_check(key.to_public(), i * 64));
+            if i > 0 {
+                ops.push(OpCode::And);
+            }
+        }
+        let signers = keys
+            .into_iter()
+            .map(|key| Box::new(StdEd25519Signer(key)) as Box<dyn Signer<Error = Infallible>>)
+            .collect();
+        Self::new(melvm::Covenant::from_ops(&ops).to_bytes(), signers)
+    }
+}
+
+/// MelVM code that pushes whether the 64 bytes at the given offset, within the signature of the input being spent, are a valid ed25519 signature of the spending transaction by the given key.
+pub(crate) fn ed25519_slot_check(pk: Ed25519PK, offset: usize) -> [OpCode; 11] {
+    let offset = offset as u32;
+    [
+        // slice out this key's signature from the signature of the input being spent
+        OpCode::PushI((offset + 64).into()),
+        OpCode::PushI(offset.into()),
+        OpCode::LoadImm(9), // spender index
+        OpCode::PushI(6u32.into()),
+        OpCode::LoadImm(0), // spender transaction
+        OpCode::VRef,
+        OpCode::VRef,
+        OpCode::BSlice,
+        OpCode::PushB(pk.0.to_vec()),
+        OpCode::LoadImm(1), // spender transaction hash
+        OpCode::SigEOk(32),
+    ]
+}
+
+impl<E: std::error::Error> Signer for MultiSigner<E> {
+    type Error = E;
+
+    fn covenant(&self) -> Bytes {
+        self.covenant.clone()
+    }
+
+    fn sig_size(&self) -> usize {
+        self.signers.iter().map(|signer| signer.sig_size()).sum()
+    }
+
+    /// collects every member's signature for the for_input-th input and puts their concatenation in the for_input-th entry of Transaction::sigs
+    fn sign(&self, txn: &Transaction, for_input: usize) -> Result<Transaction, Self::Error> {
+        let mut txn = txn.clone();
+        txn.sigs
+            .resize((for_input + 1).max(txn.sigs.len()), Bytes::new());
+        txn.sigs[for_input] = self.sign_detached(&txn, for_input)?;
         Ok(txn)
     }
+
+    fn sign_detached(&self, txn: &Transaction, for_input: usize) -> Result<Bytes, Self::Error> {
+        let mut combined = Vec::with_capacity(self.sig_size());
+        for signer in self.signers.iter() {
+            let mut sig = signer.sign_detached(txn, for_input)?.to_vec();
+            sig.resize(signer.sig_size(), 0);
+            combined.extend(sig);
+        }
+        Ok(combined.into())
+    }
+
+    /// passes the spent coin on to every member, so that each of them can show it
+    fn sign_detached_with_context(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+        spent: &CoinDataHeight,
+    ) -> Result<Bytes, Self::Error> {
+        let mut combined = Vec::with_capacity(self.sig_size());
+        for signer in self.signers.iter() {
+            let mut sig = signer
+                .sign_detached_with_context(txn, for_input, spent)?
+                .to_vec();
+            sig.resize(signer.sig_size(), 0);
+            combined.extend(sig);
+        }
+        Ok(combined.into())
+    }
 }
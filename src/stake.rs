@@ -0,0 +1,34 @@
+use bytes::Bytes;
+use melstructs::{CoinData, CoinValue, Denom, StakeDoc, Transaction, TxKind};
+
+use crate::{PrepareTxArgs, PrepareTxError, Signer, Wallet};
+
+impl Wallet {
+    /// Prepares a [TxKind::Stake] transaction that stakes `stake_amount` syms, on the terms given by the stake document. On Mel, stakes are made of syms, not MEL; the MEL coins only pay the fee.
+    ///
+    /// The staked syms go into the first output, back to this wallet's address, and the stake document, with its `syms_staked` set to `stake_amount`, is encoded into the data of the transaction. The network won't let the staked coin be spent until the stake ends, so consider [Wallet::freeze]-ing it once it confirms.
+    pub fn prepare_stake<S: Signer>(
+        &self,
+        stake_amount: CoinValue,
+        stake_params: StakeDoc,
+        signer: &S,
+        fee_multiplier: u128,
+    ) -> Result<Transaction, PrepareTxError<S::Error>> {
+        let stake_doc = StakeDoc {
+            syms_staked: stake_amount,
+            ..stake_params
+        };
+        let args = PrepareTxArgs {
+            kind: TxKind::Stake,
+            outputs: vec![CoinData {
+                covhash: self.address,
+                value: stake_amount,
+                denom: Denom::Sym,
+                additional_data: Bytes::new(),
+            }],
+            data: stdcode::serialize(&stake_doc).unwrap().into(),
+            ..Default::default()
+        };
+        self.prepare_tx(args, signer, fee_multiplier)
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::BTreeMap;
+
+use melstructs::{BlockHeight, CoinValue, Denom};
+use serde::{Deserialize, Serialize};
+
+use crate::{saturating_sum, Wallet};
+
+/// Statistics about the confirmed coins of one denomination in a [Wallet], as returned by [Wallet::utxo_stats].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DenomStats {
+    /// How many coins there are.
+    pub count: usize,
+    /// The total value of the coins.
+    pub total: CoinValue,
+    /// The value of the smallest coin.
+    pub smallest: CoinValue,
+    /// The value of the median coin. With an even number of coins, this is the smaller of the two middle values.
+    pub median: CoinValue,
+    /// The value of the largest coin.
+    pub largest: CoinValue,
+    /// The height at which the oldest coin was confirmed.
+    pub oldest_height: BlockHeight,
+}
+
+impl Wallet {
+    /// Summarizes the confirmed coins of each denomination, including those locked by pending transactions. This helps decide when to consolidate.
+    pub fn utxo_stats(&self) -> BTreeMap<Denom, DenomStats> {
+        let mut by_denom: BTreeMap<Denom, (Vec<CoinValue>, BlockHeight)> = BTreeMap::new();
+        for cdh in self.confirmed_utxos.values() {
+            let (values, oldest) = by_denom
+                .entry(cdh.coin_data.denom)
+                .or_insert((vec![], cdh.height));
+            values.push(cdh.coin_data.value);
+            *oldest = (*oldest).min(cdh.height);
+        }
+        by_denom
+            .into_iter()
+            .map(|(denom, (mut values, oldest_height))| {
+                values.sort_unstable();
+                let stats = DenomStats {
+                    count: values.len(),
+                    total: saturating_sum(values.iter().copied()),
+                    smallest: values[0],
+                    median: values[(values.len() - 1) / 2],
+                    largest: values[values.len() - 1],
+                    oldest_height,
+                };
+                (denom, stats)
+            })
+            .collect()
+    }
+
+    /// Works out what all the confirmed coins of the wallet are worth in MEL, converting the balance of every other token at the price, in MEL per unit, that the given function returns. Tokens it can't price are left out. The function is called once per token, so it can query any price oracle.
+    pub fn total_value_in_mel(&self, price: impl Fn(Denom) -> Option<f64>) -> CoinValue {
+        saturating_sum(self.balances().into_iter().filter_map(|(denom, balance)| {
+            if denom == Denom::Mel {
+                return Some(balance);
+            }
+            // float-to-int casts saturate, and negative or NaN prices count as nothing
+            price(denom).map(|price| CoinValue((balance.0 as f64 * price) as u128))
+        }))
+    }
+}
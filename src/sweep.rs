@@ -0,0 +1,51 @@
+use std::collections::BTreeMap;
+
+use melstructs::{CoinDataHeight, CoinID, Transaction};
+
+use crate::{sign_all, tally, PrepareTxError, Signer, Wallet, FEE_DENOM};
+
+impl Wallet {
+    /// Prepares a transaction that sweeps all the given external coins, such as those of an imported paper wallet, into this wallet, signed by the signer of those coins. Every coin is spent whole, with nothing left behind for the external key: the MEL, minus the fee, goes into one coin at this wallet's address, and each other denomination into another.
+    ///
+    /// The coins must all be locked by the covenant of `external_signer` and not already be in this wallet; otherwise, this fails with [PrepareTxError::BadExternalInput]. Since the fee is paid out of the swept MEL, this fails with [PrepareTxError::InsufficientFunds] if that isn't enough.
+    pub fn prepare_sweep<S: Signer>(
+        &self,
+        external_utxos: Vec<(CoinID, CoinDataHeight)>,
+        external_signer: &S,
+        fee_multiplier: u128,
+    ) -> Result<Transaction, PrepareTxError<S::Error>> {
+        let covenant = external_signer.covenant();
+        let external_address = tmelcrypt::hash_single(&covenant).into();
+        for (coin_id, cdh) in external_utxos.iter() {
+            if cdh.coin_data.covhash != external_address
+                || self.confirmed_utxos.contains_key(coin_id)
+            {
+                return Err(PrepareTxError::BadExternalInput(*coin_id));
+            }
+        }
+        let mut totals = tally(external_utxos.iter().map(|(_, cdh)| &cdh.coin_data));
+        let mel = totals.remove(&FEE_DENOM).unwrap_or_default();
+        let coins = external_utxos.iter().map(|(coin_id, _)| *coin_id).collect();
+        let spent = external_utxos.into_iter().collect();
+        let assembled = self
+            .assemble_sweep(
+                coins,
+                mel,
+                totals,
+                covenant,
+                external_signer.sig_size(),
+                fee_multiplier,
+            )
+            .map_err(|needed| PrepareTxError::InsufficientFunds {
+                denom: FEE_DENOM,
+                needed,
+                available: mel,
+            })?;
+        Ok(sign_all(
+            assembled,
+            external_signer,
+            &BTreeMap::new(),
+            &spent,
+        )?)
+    }
+}
@@ -0,0 +1,34 @@
+//! Helpers for testing code that uses this crate.
+
+use bytes::Bytes;
+use melstructs::Transaction;
+use thiserror::Error;
+
+use crate::Signer;
+
+/// A [Signer] that refuses to sign anything, for exercising the [crate::PrepareTxError::SignerRefused] path.
+pub struct RefusingSigner {
+    /// The message of the error returned on every attempt to sign.
+    pub err: String,
+}
+
+/// The error returned by [RefusingSigner].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{0}")]
+pub struct RefusedError(pub String);
+
+impl Signer for RefusingSigner {
+    type Error = RefusedError;
+
+    fn covenant(&self) -> Bytes {
+        Bytes::new()
+    }
+
+    fn sig_size(&self) -> usize {
+        64
+    }
+
+    fn sign(&self, _txn: &Transaction, _for_input: usize) -> Result<Transaction, Self::Error> {
+        Err(RefusedError(self.err.clone()))
+    }
+}
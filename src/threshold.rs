@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+use melstructs::Transaction;
+use melvm::opcode::OpCode;
+use thiserror::Error;
+use tmelcrypt::Ed25519PK;
+
+use crate::{ed25519_slot_check, Signer};
+
+/// A signer for coins locked by a k-of-n threshold covenant, assembled from partial signatures that the members produce independently, out of band.
+///
+/// Each member's partial signature is its signature of the transaction's [Transaction::hash_nosigs]. The signature of each input is the concatenation of all n members' partial signatures, in member order, with zeroes in place of the members that didn't sign.
+pub struct ThresholdSigner {
+    covenant: Bytes,
+    threshold: usize,
+    member_count: usize,
+    member_sig_size: usize,
+    partials: BTreeMap<usize, Bytes>,
+}
+
+#[derive(Error, Debug)]
+pub enum ThresholdError {
+    #[error("no member with index {0}")]
+    BadIndex(usize),
+
+    #[error("only {have} of the {need} required partial signatures are present")]
+    NotEnoughPartials { have: usize, need: usize },
+}
+
+impl ThresholdSigner {
+    /// Creates a threshold signer from the threshold covenant, the threshold k, the number of members n, and the size of each member's signature.
+    pub fn new(
+        covenant: Bytes,
+        threshold: usize,
+        member_count: usize,
+        member_sig_size: usize,
+    ) -> Self {
+        Self {
+            covenant,
+            threshold,
+            member_count,
+            member_sig_size,
+            partials: BTreeMap::new(),
+        }
+    }
+
+    /// Creates a threshold signer for ed25519 keys, with a covenant that requires valid signatures from at least `threshold` of the given keys.
+    pub fn std_ed25519(threshold: usize, keys: &[Ed25519PK]) -> Self {
+        // count the valid signatures, then check that there are more than threshold - 1
+        let mut ops = vec![OpCode::PushI((threshold.saturating_sub(1) as u64).into())];
+        for (i, key) in keys.iter().enumerate() {
+            ops.extend(ed25519_slot_check(*key, i * 64));
+            if i > 0 {
+                ops.push(OpCode::Add);
+            }
+        }
+        ops.push(OpCode::Gt);
+        Self::new(
+            melvm::Covenant::from_ops(&ops).to_bytes(),
+            threshold,
+            keys.len(),
+            64,
+        )
+    }
+
+    /// Adds the partial signature produced by the member with the given index.
+    pub fn add_partial(
+        &mut self,
+        signer_index: usize,
+        partial: Bytes,
+    ) -> Result<(), ThresholdError> {
+        if signer_index >= self.member_count {
+            return Err(ThresholdError::BadIndex(signer_index));
+        }
+        self.partials.insert(signer_index, partial);
+        Ok(())
+    }
+
+    /// Fills in the signature of every input of the transaction from the partial signatures collected so far. Fails if fewer than the threshold number of partial signatures are present.
+    pub fn finalize(&self, tx: Transaction) -> Result<Transaction, ThresholdError> {
+        let input_count = tx.inputs.len();
+        (0..input_count).try_fold(tx, |tx, i| self.sign(&tx, i))
+    }
+}
+
+impl Signer for ThresholdSigner {
+    type Error = ThresholdError;
+
+    fn covenant(&self) -> Bytes {
+        self.covenant.clone()
+    }
+
+    fn sig_size(&self) -> usize {
+        self.member_count * self.member_sig_size
+    }
+
+    /// puts the combined partial signatures in the for_input-th entry of Transaction::sigs
+    fn sign(&self, txn: &Transaction, for_input: usize) -> Result<Transaction, Self::Error> {
+        let mut txn = txn.clone();
+        txn.sigs
+            .resize((for_input + 1).max(txn.sigs.len()), Bytes::new());
+        txn.sigs[for_input] = self.sign_detached(&txn, for_input)?;
+        Ok(txn)
+    }
+
+    fn sign_detached(&self, _txn: &Transaction, _for_input: usize) -> Result<Bytes, Self::Error> {
+        if self.partials.len() < self.threshold {
+            return Err(ThresholdError::NotEnoughPartials {
+                have: self.partials.len(),
+                need: self.threshold,
+            });
+        }
+        Ok(combine_partials(
+            &self.partials,
+            self.member_count,
+            self.member_sig_size,
+        ))
+    }
+}
+
+/// Combines partial signatures, keyed by member index, into the signature a threshold covenant expects: each member's partial signature at its offset, zero-padded to `member_sig_size`, with zeroes in place of the members that didn't sign.
+pub fn combine_partials(
+    partials: &BTreeMap<usize, Bytes>,
+    member_count: usize,
+    member_sig_size: usize,
+) -> Bytes {
+    let mut combined = vec![0; member_count * member_sig_size];
+    for (&index, partial) in partials.range(..member_count) {
+        let len = partial.len().min(member_sig_size);
+        let offset = index * member_sig_size;
+        combined[offset..offset + len].copy_from_slice(&partial[..len]);
+    }
+    combined.into()
+}
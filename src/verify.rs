@@ -0,0 +1,59 @@
+use melstructs::{CoinValue, Header, Transaction};
+use thiserror::Error;
+
+use crate::Wallet;
+
+#[derive(Error, Debug)]
+pub enum VerifyError {
+    #[error("transaction lacks the covenant needed to spend input {0}")]
+    MissingCovenant(usize),
+
+    #[error("covenant rejects the transaction spending input {0}")]
+    Rejected(usize),
+}
+
+impl Wallet {
+    /// Checks that the signatures of a signed transaction actually satisfy the covenants of the inputs it spends, by running each covenant with MelVM. Only the inputs that are confirmed coins of this wallet are checked. Fails with the index of the first input that doesn't check out.
+    ///
+    /// The wallet doesn't know the latest block header, so covenants that inspect it see one with only the network and the wallet's height filled in.
+    pub fn verify_signed(&self, tx: &Transaction) -> Result<(), VerifyError> {
+        let last_header = Header {
+            network: self.netid,
+            previous: Default::default(),
+            height: self.height,
+            history_hash: Default::default(),
+            coins_hash: Default::default(),
+            transactions_hash: Default::default(),
+            fee_pool: CoinValue(0),
+            fee_multiplier: 0,
+            dosc_speed: 0,
+            pools_hash: Default::default(),
+            stakes_hash: Default::default(),
+        };
+        for (i, coin) in tx.inputs.iter().enumerate() {
+            let Some(cdh) = self.confirmed_utxos.get(coin) else {
+                continue;
+            };
+            let covenant = tx
+                .covenants
+                .iter()
+                .filter_map(|covenant| melvm::Covenant::from_bytes(covenant).ok())
+                .find(|covenant| covenant.hash() == cdh.coin_data.covhash)
+                .ok_or(VerifyError::MissingCovenant(i))?;
+            let spender_index = u8::try_from(i).map_err(|_| VerifyError::Rejected(i))?;
+            let env = melvm::CovenantEnv {
+                parent_coinid: *coin,
+                parent_cdh: cdh.clone(),
+                spender_index,
+                last_header,
+            };
+            if !covenant
+                .execute(tx, Some(env))
+                .is_some_and(|value| value.into_bool())
+            {
+                return Err(VerifyError::Rejected(i));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+mod common;
+
+use common::*;
+use melstructs::{CoinValue, Denom};
+use melwallet::{DenomValueError, Signer};
+
+#[test]
+fn max_spendable_of_mel_pays_the_fee() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let max = w.max_spendable(Denom::Mel, &s, FEE_MULTIPLIER);
+    assert!(max < CoinValue(1_000_000));
+    // a send with change pays at least the fee of sending everything to one output
+    let fee = w
+        .prepare_tx(send(vec![burn(10_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap()
+        .fee;
+    assert!(max + fee >= CoinValue(1_000_000));
+}
+
+#[test]
+fn max_spendable_of_tokens_is_the_whole_balance() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 5_000, Denom::Sym),
+            coin(addr, 3, 7_000, Denom::Sym),
+        ],
+    );
+    let max = w.max_spendable(Denom::Sym, &s, FEE_MULTIPLIER);
+    assert_eq!(max, CoinValue(12_000));
+    w.prepare_tx(send(vec![burn(max.0, Denom::Sym)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+}
+
+#[test]
+fn max_spendable_of_tokens_needs_mel_for_the_fee() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 5_000, Denom::Sym)]);
+    assert_eq!(
+        w.max_spendable(Denom::Sym, &s, FEE_MULTIPLIER),
+        CoinValue(0)
+    );
+}
+
+#[test]
+fn pending_sends_move_balances_to_pending() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 2_000_000, Denom::Mel),
+        ],
+    );
+    // a send to someone else, whose change comes back
+    let tx = w
+        .prepare_tx(send(vec![burn(400_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    let spent: u128 = tx
+        .inputs
+        .iter()
+        .map(|id| w.confirmed_utxos[id].coin_data.value.0)
+        .sum();
+    let first_fee = tx.fee;
+    assert!(tx
+        .outputs
+        .iter()
+        .any(|output| output.covhash == addr && output.value > CoinValue(0)));
+    w.add_pending(tx).unwrap();
+    assert_eq!(w.balances()[&Denom::Mel], CoinValue(3_000_000));
+    assert_eq!(
+        w.available_balances().get(&Denom::Mel).copied(),
+        Some(CoinValue(3_000_000 - spent))
+    );
+    // what's left once it confirms: the coins it doesn't spend, plus the change
+    assert_eq!(
+        w.pending_balances().get(&Denom::Mel).copied(),
+        Some(CoinValue(3_000_000 - 400_000) - first_fee)
+    );
+
+    // a send to ourselves comes back in full, less the fee
+    let tx = w
+        .prepare_tx(
+            send(vec![coin(addr, 0, 100_000, Denom::Mel).1]),
+            &s,
+            FEE_MULTIPLIER,
+        )
+        .unwrap();
+    let second_fee = tx.fee;
+    w.add_pending(tx).unwrap();
+    assert!(!w.available_balances().contains_key(&Denom::Mel));
+    assert_eq!(
+        w.pending_balances().get(&Denom::Mel).copied(),
+        Some(CoinValue(3_000_000 - 400_000) - first_fee - second_fee)
+    );
+}
+
+#[test]
+fn pending_sends_lower_the_spendable_balance_only() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 2_000_000, Denom::Mel),
+        ],
+    );
+    assert_eq!(w.spendable_balances(), w.balances());
+    let tx = w
+        .prepare_tx(send(vec![burn(1_500_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    w.add_pending(tx).unwrap();
+    assert_eq!(w.balances()[&Denom::Mel], CoinValue(3_000_000));
+    assert!(
+        w.spendable_balances()
+            .get(&Denom::Mel)
+            .copied()
+            .unwrap_or_default()
+            < CoinValue(3_000_000)
+    );
+}
+
+#[test]
+fn balance_of_one_denomination() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000, Denom::Mel),
+            coin(addr, 2, 2_000, Denom::Mel),
+            coin(addr, 3, 7, Denom::Sym),
+        ],
+    );
+    assert_eq!(w.balance(Denom::Mel), CoinValue(3_000));
+    assert_eq!(w.balance(Denom::Erg), CoinValue(0));
+    assert_eq!(w.denoms(), [Denom::Mel, Denom::Sym].into());
+}
+
+#[test]
+fn coins_of_one_denomination() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000, Denom::Mel),
+            coin(addr, 2, 3_000, Denom::Mel),
+            coin(addr, 3, 2_000, Denom::Mel),
+            coin(addr, 4, 9_000, Denom::Sym),
+        ],
+    );
+    let mut mel: Vec<_> = w.coins_for_denom(Denom::Mel).map(|(id, _)| *id).collect();
+    mel.sort();
+    let mut expected = vec![coin_id(1), coin_id(2), coin_id(3)];
+    expected.sort();
+    assert_eq!(mel, expected);
+    let (largest, cdh) = w.largest_coin(Denom::Mel).unwrap();
+    assert_eq!(largest, coin_id(2));
+    assert_eq!(cdh.coin_data.value, CoinValue(3_000));
+    assert!(w.largest_coin(Denom::Erg).is_none());
+}
+
+#[test]
+fn mel_leads_the_ordered_balances() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 10, Denom::Mel),
+            coin(addr, 2, 500, Denom::Sym),
+            coin(addr, 3, 1_000, Denom::Erg),
+        ],
+    );
+    assert_eq!(
+        w.balances_ordered(),
+        vec![
+            (Denom::Mel, CoinValue(10)),
+            (Denom::Erg, CoinValue(1_000)),
+            (Denom::Sym, CoinValue(500)),
+        ]
+    );
+}
+
+#[test]
+fn huge_balances_saturate() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, u128::MAX - 5, Denom::Sym),
+            coin(addr, 2, u128::MAX - 5, Denom::Sym),
+            coin(addr, 3, 1_000_000, Denom::Mel),
+        ],
+    );
+    assert_eq!(w.balance(Denom::Sym), CoinValue(u128::MAX));
+    assert_eq!(w.utxo_stats()[&Denom::Sym].total, CoinValue(u128::MAX));
+    // selection doesn't overflow either
+    let args = send(vec![burn(u128::MAX, Denom::Sym)]);
+    w.simulate_tx(&args, 64, s.covenant(), FEE_MULTIPLIER)
+        .unwrap();
+}
+
+#[test]
+fn total_value_converts_priced_tokens_to_mel() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 2_000, Denom::Sym),
+            coin(addr, 3, 7, Denom::Erg),
+        ],
+    );
+    // ERG has no price, so it doesn't count
+    let total = w.total_value_in_mel(|denom| match denom {
+        Denom::Sym => Some(2.5),
+        _ => None,
+    });
+    assert_eq!(total, CoinValue(1_005_000));
+}
+
+#[test]
+fn effective_balance_reserves_the_fee_in_mel_only() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 10_000, Denom::Mel),
+            coin(addr, 2, 500, Denom::Sym),
+        ],
+    );
+    assert_eq!(
+        w.effective_balance(Denom::Mel, CoinValue(300)),
+        CoinValue(9_700)
+    );
+    assert_eq!(
+        w.effective_balance(Denom::Sym, CoinValue(300)),
+        CoinValue(500)
+    );
+    assert_eq!(
+        w.effective_balance(Denom::Mel, CoinValue(20_000)),
+        CoinValue(0)
+    );
+    assert_eq!(w.effective_balance(Denom::Erg, CoinValue(1)), CoinValue(0));
+}
+
+#[test]
+fn typed_balances_refuse_to_mix_denominations() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000, Denom::Mel),
+            coin(addr, 2, 5, Denom::Sym),
+        ],
+    );
+    let balances = w.typed_balances();
+    assert_eq!(balances.len(), 2);
+    let mel = *balances.iter().find(|b| b.denom == Denom::Mel).unwrap();
+    let sym = *balances.iter().find(|b| b.denom == Denom::Sym).unwrap();
+    assert_eq!(
+        mel.checked_add(sym),
+        Err(DenomValueError::Mismatch {
+            left: Denom::Mel,
+            right: Denom::Sym
+        })
+    );
+    assert_eq!(mel.checked_add(mel).unwrap().value, CoinValue(2_000));
+    assert_eq!(
+        sym.checked_sub(sym.checked_add(sym).unwrap()),
+        Err(DenomValueError::OutOfRange(Denom::Sym))
+    );
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use bytes::Bytes;
+use common::*;
+use melstructs::{Address, CoinData, CoinValue, Denom, TxKind};
+use melwallet::{CoinDataExt, PrepareTxArgs, Signer, TemplateError, TxTemplate};
+
+#[test]
+fn builder_matches_hand_written_args() {
+    let other = Address(tmelcrypt::hash_single(b"other"));
+    let (vault_coin, vault_cdh) = external(other, 1, 5000, Denom::Mel);
+    let built = PrepareTxArgs::builder()
+        .output(Address::coin_destroy(), Denom::Mel, CoinValue(10_000))
+        .output(other, Denom::Sym, CoinValue(7))
+        .kind(TxKind::Faucet)
+        .data(b"hello".as_slice())
+        .extra_input(vault_coin, vault_cdh.clone())
+        .extra_covenant(Bytes::from_static(b"covenant"))
+        .build();
+    let by_hand = PrepareTxArgs {
+        kind: TxKind::Faucet,
+        inputs: vec![(vault_coin, vault_cdh)],
+        outputs: vec![
+            burn(10_000, Denom::Mel),
+            CoinData {
+                covhash: other,
+                value: CoinValue(7),
+                denom: Denom::Sym,
+                additional_data: Bytes::new(),
+            },
+        ],
+        covenants: vec![Bytes::from_static(b"covenant")],
+        data: Bytes::from_static(b"hello"),
+        ..Default::default()
+    };
+    assert_eq!(built, by_hand);
+}
+
+#[test]
+fn outputs_to_covenants_hash_the_covenant() {
+    let s = signer();
+    let covenant = melvm::Covenant::from_bytes(&s.covenant()).unwrap();
+    let output = CoinData::to_covenant(&covenant, Denom::Sym, CoinValue(7), b"unlock".as_slice());
+    assert_eq!(output.covhash, covenant.hash());
+    assert_eq!(output.covhash, wallet(&s).address);
+    assert_eq!(output.denom, Denom::Sym);
+    assert_eq!(output.value, CoinValue(7));
+    assert_eq!(output.additional_data, Bytes::from_static(b"unlock"));
+}
+
+#[test]
+fn templates_fill_in_amounts() {
+    let other = Address(tmelcrypt::hash_single(b"other"));
+    let template = TxTemplate {
+        kind: TxKind::Normal,
+        data: Bytes::from_static(b"payroll"),
+        recipients: vec![(Address::coin_destroy(), Denom::Mel), (other, Denom::Sym)],
+    };
+    let january = template
+        .instantiate(&[CoinValue(100), CoinValue(7)])
+        .unwrap();
+    let february = template
+        .instantiate(&[CoinValue(200), CoinValue(9)])
+        .unwrap();
+    assert_eq!(january.data, template.data);
+    assert_eq!(january.outputs[0], burn(100, Denom::Mel));
+    assert_eq!(
+        (
+            february.outputs[1].covhash,
+            february.outputs[1].denom,
+            february.outputs[1].value
+        ),
+        (other, Denom::Sym, CoinValue(9))
+    );
+
+    let reloaded: TxTemplate =
+        serde_json::from_str(&serde_json::to_string(&template).unwrap()).unwrap();
+    assert_eq!(reloaded, template);
+    assert_eq!(
+        reloaded.instantiate(&[CoinValue(100)]),
+        Err(TemplateError::WrongAmountCount {
+            recipients: 2,
+            amounts: 1
+        })
+    );
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use bytes::Bytes;
+use common::*;
+use melstructs::{Address, CoinValue, Denom};
+use melwallet::PrepareTxArgs;
+
+#[test]
+fn change_goes_to_the_change_address() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let elsewhere = Address(tmelcrypt::hash_single(b"savings"));
+    let args = PrepareTxArgs {
+        change_address: Some(elsewhere),
+        ..send(vec![burn(100_000, Denom::Mel)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(tx.outputs.len(), 2);
+    assert!(tx.outputs.iter().all(|output| output.covhash != addr));
+    let change = tx
+        .outputs
+        .iter()
+        .find(|output| output.covhash == elsewhere)
+        .unwrap();
+    assert_eq!(change.value + tx.fee, CoinValue(900_000));
+}
+
+#[test]
+fn change_can_be_split() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let args = PrepareTxArgs {
+        split_change: 4,
+        ..send(vec![burn(100_000, Denom::Mel)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    let change: Vec<u128> = tx
+        .outputs
+        .iter()
+        .filter(|output| output.covhash == addr)
+        .map(|output| output.value.0)
+        .collect();
+    assert_eq!(change.len(), 4);
+    assert_eq!(change.iter().sum::<u128>() + tx.fee.0, 900_000);
+    let (min, max) = (change.iter().min().unwrap(), change.iter().max().unwrap());
+    assert!(max - min <= 1);
+}
+
+#[test]
+fn change_carries_the_change_data() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 500, Denom::Sym),
+        ],
+    );
+    let args = PrepareTxArgs {
+        change_data: Bytes::from_static(b"change"),
+        ..send(vec![burn(100_000, Denom::Mel), burn(100, Denom::Sym)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(tx.outputs.len(), 4);
+    for output in tx.outputs.iter() {
+        if output.covhash == addr {
+            assert_eq!(output.additional_data, Bytes::from_static(b"change"));
+        } else {
+            assert!(output.additional_data.is_empty());
+        }
+    }
+}
+
+#[test]
+fn previewed_change_matches_the_prepared_change() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 500, Denom::Sym),
+        ],
+    );
+    let args = PrepareTxArgs {
+        split_change: 2,
+        ..send(vec![burn(1_000, Denom::Mel), burn(200, Denom::Sym)])
+    };
+    let tx = w.prepare_tx(args.clone(), &s, FEE_MULTIPLIER).unwrap();
+    let mut previewed = w.preview_change(&args, tx.fee).unwrap();
+    let mut prepared: Vec<_> = tx
+        .outputs
+        .iter()
+        .filter(|output| output.covhash == addr)
+        .cloned()
+        .collect();
+    assert_eq!(previewed.len(), 4);
+    previewed.sort();
+    prepared.sort();
+    assert_eq!(previewed, prepared);
+}
+
+#[test]
+fn isolated_change_is_one_output_at_the_end() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    // recipients worth more than the change, so that sorting by value would put the change first
+    let args = PrepareTxArgs {
+        change_isolation: true,
+        split_change: 4,
+        ..send(vec![burn(600_000, Denom::Mel), burn(300_000, Denom::Mel)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    let change: Vec<usize> = tx
+        .outputs
+        .iter()
+        .enumerate()
+        .filter(|(_, output)| output.covhash == addr)
+        .map(|(i, _)| i)
+        .collect();
+    assert_eq!(tx.outputs.len(), 3);
+    assert_eq!(change, vec![2]);
+}
@@ -0,0 +1,218 @@
+mod common;
+
+use common::*;
+use melstructs::{CoinID, Denom, Transaction};
+use melwallet::{CoinSelectionStrategy, PrepareTxArgs, Signer, Wallet};
+
+/// A wallet with plenty of MEL, and SYM coins of 300, 100 and 500, confirmed in that order.
+fn sym_wallet(s: &impl Signer) -> Wallet {
+    let mut w = wallet(s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 300, Denom::Sym),
+            coin(addr, 0, 1_000_000, Denom::Mel),
+        ],
+    );
+    fund(&mut w, vec![coin(addr, 2, 100, Denom::Sym)]);
+    fund(&mut w, vec![coin(addr, 3, 500, Denom::Sym)]);
+    w
+}
+
+/// The values of the SYM coins that the transaction spends, smallest first.
+fn sym_inputs(w: &Wallet, tx: &Transaction) -> Vec<u128> {
+    let mut values: Vec<u128> = tx
+        .inputs
+        .iter()
+        .map(|id| &w.confirmed_utxos[id].coin_data)
+        .filter(|coin| coin.denom == Denom::Sym)
+        .map(|coin| coin.value.0)
+        .collect();
+    values.sort();
+    values
+}
+
+fn pick(w: &Wallet, s: &impl Signer, strategy: CoinSelectionStrategy, sym: u128) -> Vec<u128> {
+    let args = PrepareTxArgs {
+        coin_selection: strategy,
+        ..send(vec![burn(sym, Denom::Sym)])
+    };
+    sym_inputs(w, &w.prepare_tx(args, s, FEE_MULTIPLIER).unwrap())
+}
+
+#[test]
+fn strategies_pick_the_expected_coins() {
+    let s = signer();
+    let w = sym_wallet(&s);
+    assert_eq!(
+        pick(&w, &s, CoinSelectionStrategy::SmallestFirst, 250),
+        vec![100, 300]
+    );
+    assert_eq!(
+        pick(&w, &s, CoinSelectionStrategy::LargestFirst, 250),
+        vec![500]
+    );
+    assert_eq!(pick(&w, &s, CoinSelectionStrategy::Oldest, 250), vec![300]);
+    for _ in 0..10 {
+        let picked = pick(&w, &s, CoinSelectionStrategy::Random, 250);
+        assert!(picked.iter().sum::<u128>() >= 250);
+    }
+}
+
+#[test]
+fn branch_and_bound_handles_huge_wallets() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    let coins = (0..60_000)
+        .map(|i| coin(addr, i, 1000, Denom::Sym))
+        .collect();
+    fund(&mut w, coins);
+    fund(&mut w, vec![coin(addr, 60_000, 1_000_000_000, Denom::Mel)]);
+    let args = PrepareTxArgs {
+        coin_selection: CoinSelectionStrategy::BranchAndBound,
+        ..send(vec![burn(50_000_500, Denom::Sym)])
+    };
+    // no subset adds up exactly, so the search goes tens of thousands of coins deep before giving up, which used to overflow the stack
+    let tx = w
+        .prepare_unsigned(&args, s.sig_size(), s.covenant(), FEE_MULTIPLIER)
+        .unwrap();
+    assert!(tx.inputs.len() > 50_000);
+}
+
+#[test]
+fn branch_and_bound_avoids_change() {
+    let s = signer();
+    let w = sym_wallet(&s);
+    let args = PrepareTxArgs {
+        coin_selection: CoinSelectionStrategy::BranchAndBound,
+        ..send(vec![burn(400, Denom::Sym)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(sym_inputs(&w, &tx), vec![100, 300]);
+    assert_eq!(
+        tx.outputs
+            .iter()
+            .filter(|output| output.denom == Denom::Sym)
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn tidy_selection_avoids_change_for_the_fee() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 600, Denom::Mel),
+            coin(addr, 2, 700, Denom::Mel),
+            coin(addr, 3, 800, Denom::Mel),
+            coin(addr, 4, 900, Denom::Mel),
+            coin(addr, 5, 2_000_000, Denom::Mel),
+            coin(addr, 6, 500, Denom::Sym),
+            coin(addr, 7, 500, Denom::Erg),
+        ],
+    );
+    let prepare = |strategy| {
+        let args = PrepareTxArgs {
+            coin_selection: strategy,
+            ..send(vec![burn(100, Denom::Sym)])
+        };
+        w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap()
+    };
+    let mel_change = |tx: &Transaction| {
+        tx.outputs
+            .iter()
+            .filter(|output| output.covhash == addr && output.denom == Denom::Mel)
+            .count()
+    };
+    // the big coin alone pays the fee, but leaves MEL change
+    let largest = prepare(CoinSelectionStrategy::LargestFirst);
+    assert_eq!(mel_change(&largest), 1);
+    // the small coins pay it exactly, leaving only the change of the token sent
+    let tidy = prepare(CoinSelectionStrategy::Tidy);
+    assert_eq!(mel_change(&tidy), 0);
+    assert!(!tidy.inputs.contains(&coin_id(5)));
+    assert!(tidy.inputs.contains(&coin_id(6)));
+    assert!(!tidy.inputs.contains(&coin_id(7)));
+    assert_eq!(tidy.outputs.len(), 2);
+}
+
+#[test]
+fn tidy_selection_falls_back_to_the_smallest_coin_that_is_enough() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 50_000, Denom::Mel),
+            coin(addr, 2, 60_000, Denom::Mel),
+            coin(addr, 3, 2_000_000, Denom::Mel),
+            coin(addr, 4, 500, Denom::Sym),
+        ],
+    );
+    // no set of coins pays the fee exactly, so one coin does, with a single change output
+    let args = PrepareTxArgs {
+        coin_selection: CoinSelectionStrategy::Tidy,
+        ..send(vec![burn(100, Denom::Sym)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    let mut inputs = tx.inputs.clone();
+    inputs.sort_unstable();
+    let mut expected = vec![coin_id(1), coin_id(4)];
+    expected.sort_unstable();
+    assert_eq!(inputs, expected);
+}
+
+#[test]
+fn required_inputs_match_the_prepared_inputs() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        (1..=6)
+            .map(|i| coin(addr, i, 1_000 * i as u128, Denom::Mel))
+            .collect(),
+    );
+    let args = send(vec![burn(7_500, Denom::Mel)]);
+    let tx = w.prepare_tx(args.clone(), &s, FEE_MULTIPLIER).unwrap();
+    let inputs = w.required_inputs_for(&args, tx.fee).unwrap();
+    let total: u128 = inputs.iter().map(|(_, cdh)| cdh.coin_data.value.0).sum();
+    assert!(total >= 7_500 + tx.fee.0);
+    let mut required: Vec<CoinID> = inputs.iter().map(|(coin, _)| *coin).collect();
+    let mut prepared = tx.inputs.clone();
+    required.sort();
+    prepared.sort();
+    assert_eq!(required, prepared);
+}
+
+#[test]
+fn seeded_random_selection_is_reproducible() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        (1..=30)
+            .map(|i| coin(addr, i, 10_000, Denom::Mel))
+            .collect(),
+    );
+    let pick = |seed| {
+        let args = PrepareTxArgs {
+            coin_selection: CoinSelectionStrategy::SeededRandom(seed),
+            ..send(vec![burn(25_000, Denom::Mel)])
+        };
+        let mut inputs = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap().inputs;
+        inputs.sort();
+        inputs
+    };
+    assert_eq!(pick(7), pick(7));
+    assert_eq!(pick(42), pick(42));
+    assert!((0..5).any(|seed| pick(seed) != pick(seed + 100)));
+}
@@ -0,0 +1,236 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::*;
+use melstructs::{BlockHeight, CoinID, CoinValue, Denom, NetID};
+use melwallet::{
+    CoinSelectionStrategy, CoinStatus, PrepareTxArgs, PrepareTxError, Wallet, MAX_ROLLBACK_DEPTH,
+};
+
+#[test]
+fn immature_coins_are_not_spent() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    w.set_maturity(coin_id(1), BlockHeight(100));
+    w.add_coins_at(NetID::Testnet, BlockHeight(50), vec![], vec![])
+        .unwrap();
+    assert_eq!(w.immature_coins(), vec![(coin_id(1), BlockHeight(100))]);
+    assert!(matches!(
+        w.prepare_tx(send(vec![burn(10_000, Denom::Mel)]), &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::InsufficientFunds { .. })
+    ));
+    w.add_coins_at(NetID::Testnet, BlockHeight(100), vec![], vec![])
+        .unwrap();
+    assert!(w.immature_coins().is_empty());
+    assert_eq!(
+        w.spendable_balances().get(&Denom::Mel).copied(),
+        Some(CoinValue(1_000_000))
+    );
+    w.prepare_tx(send(vec![burn(10_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+}
+
+#[test]
+fn frozen_coins_are_not_spent() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    w.freeze(coin_id(1));
+    assert_eq!(w.balance(Denom::Mel), CoinValue(1_000_000));
+    assert!(w.spendable_balances().is_empty());
+    assert!(matches!(
+        w.prepare_tx(send(vec![burn(10_000, Denom::Mel)]), &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::InsufficientFunds { .. })
+    ));
+    // freezes are saved with the wallet
+    let mut w = Wallet::from_bytes(&w.to_bytes()).unwrap();
+    assert!(w.frozen.contains(&coin_id(1)));
+    w.unfreeze(coin_id(1));
+    w.prepare_tx(send(vec![burn(10_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+}
+
+#[test]
+fn spent_coins_lose_their_maturity_and_freeze() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 5000, Denom::Mel),
+            coin(addr, 2, 5000, Denom::Mel),
+        ],
+    );
+    let (locked, _) = coin(addr, 1, 5000, Denom::Mel);
+    let (frozen, _) = coin(addr, 2, 5000, Denom::Mel);
+    w.set_maturity(locked, BlockHeight(1000));
+    w.freeze(frozen);
+    w.add_coins(NetID::Testnet, BlockHeight(2), vec![], vec![locked, frozen])
+        .unwrap();
+    // still remembered while the spend can be rolled back
+    assert!(w.maturity.contains_key(&locked));
+    assert!(w.frozen.contains(&frozen));
+    for _ in 0..MAX_ROLLBACK_DEPTH {
+        fund(&mut w, vec![]);
+    }
+    assert!(w.maturity.is_empty());
+    assert!(w.frozen.is_empty());
+}
+
+#[test]
+fn rolled_away_coins_lose_their_maturity_and_freeze() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![]);
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 5000, Denom::Mel),
+            coin(addr, 2, 5000, Denom::Mel),
+        ],
+    );
+    w.set_maturity(coin_id(1), BlockHeight(1000));
+    w.freeze(coin_id(2));
+    w.rollback_to(BlockHeight(1)).unwrap();
+    assert!(w.maturity.is_empty());
+    assert!(w.frozen.is_empty());
+}
+
+#[test]
+fn recent_coins_lack_confirmations() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    w.add_coins_at(
+        NetID::Testnet,
+        BlockHeight(10),
+        vec![coin(addr, 2, 5_000_000, Denom::Mel)],
+        vec![],
+    )
+    .unwrap();
+    assert_eq!(w.confirmations(&coin_id(2)), Some(1));
+    assert_eq!(w.confirmations(&coin_id(1)), Some(10));
+
+    let mut args = PrepareTxArgs {
+        min_confirmations: BlockHeight(6),
+        coin_selection: CoinSelectionStrategy::LargestFirst,
+        ..send(vec![burn(1_000, Denom::Mel)])
+    };
+    let tx = w.prepare_tx(args.clone(), &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(tx.inputs, vec![coin_id(1)]);
+    args.outputs[0].value = CoinValue(2_000_000);
+    assert!(matches!(
+        w.prepare_tx(args, &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::InsufficientFunds {
+            denom: Denom::Mel,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn all_coins_report_their_status() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 2_000_000, Denom::Mel),
+            coin(addr, 3, 3_000_000, Denom::Mel),
+        ],
+    );
+    w.freeze(coin_id(2));
+    let args = PrepareTxArgs {
+        inputs: vec![(coin_id(1), w.confirmed_utxos[&coin_id(1)].clone())],
+        coin_control: true,
+        ..send(vec![burn(1_000, Denom::Mel)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    let txhash = tx.hash_nosigs();
+    w.add_pending(tx).unwrap();
+
+    let statuses: BTreeMap<CoinID, CoinStatus> = w
+        .all_coins()
+        .map(|(coin, _, status)| (coin, status))
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            (coin_id(1), CoinStatus::LockedByPending(txhash)),
+            (coin_id(2), CoinStatus::Frozen),
+            (coin_id(3), CoinStatus::Confirmed),
+        ]
+        .into()
+    );
+}
+
+#[test]
+fn reserved_coins_are_not_spent_until_released() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 1_000_000, Denom::Mel),
+        ],
+    );
+    let args = send(vec![burn(500_000, Denom::Mel)]);
+    let first = w.reserve(&[coin_id(1)]);
+    let tx = w.prepare_tx(args.clone(), &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(tx.inputs, vec![coin_id(2)]);
+    assert!(w
+        .all_coins()
+        .any(|(coin, _, status)| coin == coin_id(1) && status == CoinStatus::Reserved(first)));
+
+    let second = w.reserve(&[coin_id(2)]);
+    assert_ne!(first, second);
+    assert!(w.prepare_tx(args.clone(), &s, FEE_MULTIPLIER).is_err());
+    w.release(first);
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(tx.inputs, vec![coin_id(1)]);
+
+    // reservations are transient, so they aren't persisted, nor compared
+    let restored = Wallet::from_bytes(&w.to_bytes()).unwrap();
+    assert!(restored.reservations.is_empty());
+    assert_eq!(restored, w);
+}
+
+#[test]
+fn reservations_are_dropped_with_their_coins() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    fund(&mut w, vec![coin(addr, 2, 1_000_000, Denom::Mel)]);
+    w.reserve(&[coin_id(1)]);
+    let rolled_back = w.reserve(&[coin_id(2)]);
+
+    // spent by a confirmed block, say from another copy of the wallet
+    let height = w.next_height();
+    w.add_coins(NetID::Testnet, height, vec![], vec![coin_id(1)])
+        .unwrap();
+    assert_eq!(w.reservations.reservation_of(&coin_id(1)), None);
+    assert_eq!(
+        w.reservations.reservation_of(&coin_id(2)),
+        Some(rolled_back)
+    );
+
+    // the block that gave us the coin is rolled back
+    w.rollback_to(BlockHeight(1)).unwrap();
+    assert!(w.reservations.is_empty());
+    // the spent coin is back, but no longer reserved
+    assert!(w
+        .all_coins()
+        .any(|(coin, _, status)| coin == coin_id(1) && status == CoinStatus::Confirmed));
+}
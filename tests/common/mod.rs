@@ -0,0 +1,110 @@
+#![allow(dead_code)]
+
+use bytes::Bytes;
+use melstructs::{
+    Address, CoinData, CoinDataHeight, CoinID, CoinValue, Denom, NetID, Transaction, TxHash,
+};
+use melwallet::{PrepareTxArgs, Signer, StdEd25519Signer, Wallet};
+
+/// A signer with a fresh random key.
+pub fn signer() -> StdEd25519Signer {
+    StdEd25519Signer(tmelcrypt::Ed25519SK::generate())
+}
+
+/// An empty testnet wallet for the given signer.
+pub fn wallet(signer: &impl Signer) -> Wallet {
+    Wallet::new(
+        NetID::Testnet,
+        tmelcrypt::hash_single(signer.covenant()).into(),
+    )
+}
+
+/// A coin at the given address, with an ID derived from `i`.
+pub fn coin(address: Address, i: u32, value: u128, denom: Denom) -> (CoinID, CoinData) {
+    (
+        coin_id(i),
+        CoinData {
+            covhash: address,
+            value: CoinValue(value),
+            denom,
+            additional_data: Bytes::new(),
+        },
+    )
+}
+
+/// A coin ID derived from `i`.
+pub fn coin_id(i: u32) -> CoinID {
+    CoinID::new(TxHash(tmelcrypt::hash_single(i.to_be_bytes())), 0)
+}
+
+/// An output sending `value` of `denom` to an address nobody controls.
+pub fn burn(value: u128, denom: Denom) -> CoinData {
+    CoinData {
+        covhash: Address::coin_destroy(),
+        value: CoinValue(value),
+        denom,
+        additional_data: Bytes::new(),
+    }
+}
+
+/// Arguments for a transaction with the given outputs, and defaults otherwise.
+pub fn send(outputs: Vec<CoinData>) -> PrepareTxArgs {
+    PrepareTxArgs {
+        outputs,
+        ..Default::default()
+    }
+}
+
+/// Gives the wallet the given coins in the next block.
+pub fn fund(wallet: &mut Wallet, coins: Vec<(CoinID, CoinData)>) {
+    let height = wallet.next_height();
+    wallet
+        .add_coins(NetID::Testnet, height, coins, vec![])
+        .unwrap();
+}
+
+/// Confirms the transaction in the next block, giving the wallet the outputs back to its address and spending the inputs.
+pub fn confirm(wallet: &mut Wallet, tx: &Transaction) {
+    let height = wallet.next_height();
+    let received: Vec<(CoinID, CoinData)> = tx
+        .outputs
+        .iter()
+        .enumerate()
+        .filter(|(_, output)| output.covhash == wallet.address)
+        .map(|(i, output)| (tx.output_coinid(i as u8), output.clone()))
+        .collect();
+    wallet
+        .add_coins(NetID::Testnet, height, received, tx.inputs.clone())
+        .unwrap();
+}
+
+/// The fee multiplier used throughout the tests.
+pub const FEE_MULTIPLIER: u128 = 1 << 16;
+
+/// The minimum fee of the transaction at [FEE_MULTIPLIER].
+pub fn base_fee(tx: &Transaction) -> CoinValue {
+    tx.base_fee(FEE_MULTIPLIER, 0, melvm::covenant_weight_from_bytes)
+}
+
+/// A coin of another address, confirmed at height 1, for use as an external input.
+pub fn external(address: Address, i: u32, value: u128, denom: Denom) -> (CoinID, CoinDataHeight) {
+    let (id, coin_data) = coin(address, i, value, denom);
+    (
+        id,
+        CoinDataHeight {
+            coin_data,
+            height: 1.into(),
+        },
+    )
+}
+
+/// Runs a future that never actually waits, such as one from a synchronous signer, to completion.
+pub fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+    let mut fut = std::pin::pin!(fut);
+    let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+    loop {
+        if let std::task::Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+            return out;
+        }
+    }
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::*;
+use melstructs::{CoinValue, Denom};
+use melwallet::PrepareTxError;
+
+#[test]
+fn consolidation_merges_the_smallest_coins() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 10_000, Denom::Mel),
+            coin(addr, 2, 20_000, Denom::Mel),
+            coin(addr, 3, 30_000, Denom::Mel),
+            coin(addr, 4, 1_000_000, Denom::Mel),
+        ],
+    );
+    let tx = w
+        .prepare_consolidation(Denom::Mel, 3, &s, FEE_MULTIPLIER)
+        .unwrap();
+    assert_eq!(tx.inputs.len(), 3);
+    assert!(!tx.inputs.contains(&coin_id(4)));
+    assert_eq!(tx.outputs.len(), 1);
+    assert_eq!(tx.outputs[0].covhash, addr);
+    assert_eq!(tx.outputs[0].value + tx.fee, CoinValue(60_000));
+}
+
+#[test]
+fn consolidation_needs_two_coins() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 5_000, Denom::Sym),
+        ],
+    );
+    assert!(matches!(
+        w.prepare_consolidation(Denom::Sym, 10, &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::NothingToConsolidate(Denom::Sym))
+    ));
+    assert!(matches!(
+        w.prepare_consolidation(Denom::Mel, 10, &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::NothingToConsolidate(Denom::Mel))
+    ));
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use common::*;
+use melstructs::Denom;
+
+#[test]
+fn diffs_list_what_changed() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let before = w.clone();
+    assert!(before.diff(&w).is_empty());
+
+    fund(&mut w, vec![coin(addr, 2, 5_000, Denom::Mel)]);
+    let tx = w
+        .prepare_tx(send(vec![burn(1_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    w.add_pending(tx.clone()).unwrap();
+    let diff = before.diff(&w);
+    assert_eq!(
+        diff.added_utxos,
+        vec![(coin_id(2), w.confirmed_utxos[&coin_id(2)].clone())]
+    );
+    assert!(diff.removed_utxos.is_empty());
+    assert_eq!(diff.added_pending, vec![tx.clone()]);
+    assert!(diff.removed_pending.is_empty());
+
+    let back = w.diff(&before);
+    assert!(back.added_utxos.is_empty());
+    assert_eq!(back.removed_utxos, vec![coin_id(2)]);
+    assert!(back.added_pending.is_empty());
+    assert_eq!(back.removed_pending, vec![tx.hash_nosigs()]);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::*;
+use melstructs::{Address, Denom, NetID};
+use melwallet::{migrate, MigrationError, Wallet};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+#[test]
+fn wallets_round_trip_through_bytes() {
+    let s = signer();
+    for seed in 0..20 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut w = wallet(&s);
+        let addr = w.address;
+        for block in 0..rng.gen_range(1..10) {
+            let coins = (0..rng.gen_range(0..50))
+                .map(|i| {
+                    let denom = [Denom::Mel, Denom::Sym, Denom::Erg][rng.gen_range(0..3)];
+                    coin(addr, block * 1000 + i, rng.gen_range(1..1_000_000), denom)
+                })
+                .collect();
+            fund(&mut w, coins);
+        }
+        if let Ok(tx) = w.prepare_tx(send(vec![burn(1000, Denom::Mel)]), &s, FEE_MULTIPLIER) {
+            w.add_pending(tx).unwrap();
+        }
+        if let Some(&coin) = w.confirmed_utxos.keys().next() {
+            w.freeze(coin);
+        }
+        assert_eq!(Wallet::from_bytes(&w.to_bytes()).unwrap(), w);
+    }
+}
+
+#[test]
+fn migrates_v0_and_v1() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 5000, Denom::Mel)]);
+    let v0 = stdcode::serialize(&w).unwrap();
+    assert_eq!(migrate(&v0).unwrap(), w);
+    assert_eq!(migrate(&w.to_bytes()).unwrap(), w);
+}
+
+#[test]
+fn v0_wallets_that_look_tagged_still_migrate() {
+    // some of these start with bytes that also parse as a tagged blob
+    for b in 0..=u8::MAX {
+        let w = Wallet::new(NetID::Testnet, Address(tmelcrypt::HashVal([b; 32])));
+        let v0 = stdcode::serialize(&w).unwrap();
+        assert_eq!(migrate(&v0).unwrap(), w);
+    }
+}
+
+#[test]
+fn blobs_with_duplicate_coins_are_malformed() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 2_000_000, Denom::Mel),
+        ],
+    );
+    // overwrite the ID of the second coin with that of the first
+    let mut blob = stdcode::serialize(&w).unwrap();
+    let second = coin_id(2).txhash.0 .0;
+    let pos = blob
+        .windows(32)
+        .position(|window| window == second)
+        .unwrap();
+    blob[pos..pos + 32].copy_from_slice(&coin_id(1).txhash.0 .0);
+    // the decoding error comes through as is
+    assert!(matches!(
+        Wallet::from_bytes(&blob),
+        Err(MigrationError::Malformed(e))
+            if matches!(&*e, bincode::ErrorKind::Custom(msg) if msg.starts_with("duplicate key"))
+    ));
+}
+
+#[test]
+fn blobs_with_foreign_coins_are_rejected() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let (foreign, cdh) = external(Address::coin_destroy(), 2, 5, Denom::Mel);
+    w.confirmed_utxos.insert(foreign, cdh);
+    assert!(matches!(
+        Wallet::from_bytes(&w.to_bytes()),
+        Err(MigrationError::WrongAddress(coin)) if coin == foreign
+    ));
+}
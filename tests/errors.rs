@@ -0,0 +1,89 @@
+mod common;
+
+use bytes::Bytes;
+use common::*;
+use melstructs::{Denom, Transaction};
+use melwallet::{ErasedSignerError, PrepareTxError, Signer};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("the device is unplugged")]
+struct Unplugged;
+
+/// Signs like the wrapped signer, but fails on every attempt to sign.
+struct UnpluggedSigner<S>(S);
+
+impl<S: Signer> Signer for UnpluggedSigner<S> {
+    type Error = Unplugged;
+
+    fn covenant(&self) -> Bytes {
+        self.0.covenant()
+    }
+
+    fn sig_size(&self) -> usize {
+        self.0.sig_size()
+    }
+
+    fn sign(&self, _txn: &Transaction, _for_input: usize) -> Result<Transaction, Self::Error> {
+        Err(Unplugged)
+    }
+}
+
+#[test]
+fn errors_of_different_signers_can_be_erased() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let unplugged = UnpluggedSigner(signer());
+    let mut unplugged_wallet = wallet(&unplugged);
+    let addr = unplugged_wallet.address;
+    fund(
+        &mut unplugged_wallet,
+        vec![coin(addr, 1, 1_000_000, Denom::Mel)],
+    );
+    let errors: Vec<PrepareTxError<ErasedSignerError>> = vec![
+        w.prepare_tx(send(vec![burn(1, Denom::Mel)]), &s, FEE_MULTIPLIER)
+            .unwrap_err()
+            .erase(),
+        unplugged_wallet
+            .prepare_tx(
+                send(vec![burn(10_000, Denom::Mel)]),
+                &unplugged,
+                FEE_MULTIPLIER,
+            )
+            .unwrap_err()
+            .erase(),
+    ];
+    assert!(matches!(errors[0], PrepareTxError::DustOutput(_)));
+    assert!(matches!(
+        &errors[1],
+        PrepareTxError::SignerRefused(e) if e.to_string() == "the device is unplugged"
+    ));
+    let boxed: Box<dyn std::error::Error> = Box::new(errors.into_iter().nth(1).unwrap());
+    assert_eq!(
+        boxed.to_string(),
+        "signer refused to sign with error: the device is unplugged"
+    );
+}
+
+#[test]
+fn refusals_surface_unchanged() {
+    use melwallet::test_util::{RefusedError, RefusingSigner};
+
+    let s = RefusingSigner {
+        err: "not today".into(),
+    };
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let args = send(vec![burn(10_000, Denom::Mel)]);
+    assert!(matches!(
+        w.prepare_tx(args.clone(), &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::SignerRefused(RefusedError(e))) if e == "not today"
+    ));
+    assert!(matches!(
+        block_on(w.prepare_tx_async(args, &s, FEE_MULTIPLIER)),
+        Err(PrepareTxError::SignerRefused(RefusedError(e))) if e == "not today"
+    ));
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use common::*;
+use melstructs::{CoinValue, Denom};
+use melwallet::{FeePolicy, PrepareTxError, FAST_FEE_FACTOR};
+
+#[test]
+fn fee_policies_scale_the_fee() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 100_000_000, Denom::Mel)]);
+    let args = send(vec![burn(1_000, Denom::Mel)]);
+    let fee = |policy| {
+        w.prepare_tx_with_policy(args.clone(), &s, FEE_MULTIPLIER, policy)
+            .unwrap()
+            .fee
+    };
+
+    let minimum = fee(FeePolicy::Minimum);
+    let economical = fee(FeePolicy::Economical(1.2));
+    let fast = fee(FeePolicy::Fast(3.0));
+    assert!(minimum < economical);
+    assert!(economical < fast);
+    assert_eq!(
+        fee(FeePolicy::Exact(CoinValue(123_456))),
+        CoinValue(123_456)
+    );
+    // economical never pays less than the minimum, nor as much as fast does
+    assert_eq!(fee(FeePolicy::Economical(0.1)), minimum);
+    assert_eq!(
+        fee(FeePolicy::Economical(100.0)),
+        fee(FeePolicy::Fast(FAST_FEE_FACTOR))
+    );
+    // and fast always pays at least that
+    assert_eq!(
+        fee(FeePolicy::Fast(0.0)),
+        fee(FeePolicy::Fast(FAST_FEE_FACTOR))
+    );
+    assert!(minimum < fee(FeePolicy::Fast(0.0)));
+}
+
+#[test]
+fn nonsense_fee_factors_are_refused() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 100_000_000, Denom::Mel)]);
+    let args = send(vec![burn(1_000, Denom::Mel)]);
+    for policy in [
+        FeePolicy::Economical(-1.0),
+        FeePolicy::Fast(f64::NAN),
+        FeePolicy::Fast(f64::INFINITY),
+    ] {
+        assert_eq!(policy.fee_multiplier(FEE_MULTIPLIER), None);
+        assert!(matches!(
+            w.prepare_tx_with_policy(args.clone(), &s, FEE_MULTIPLIER, policy),
+            Err(PrepareTxError::BadFeeFactor(_))
+        ));
+    }
+}
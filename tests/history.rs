@@ -0,0 +1,85 @@
+mod common;
+
+use common::*;
+use melstructs::{BlockHeight, CoinID, Denom, TxKind};
+
+#[test]
+fn history_records_coins_in_and_out() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    let (received, _) = coin(addr, 1, 1_000_000, Denom::Mel);
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let tx = w
+        .prepare_tx(send(vec![burn(10_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    w.add_pending(tx.clone()).unwrap();
+    confirm(&mut w, &tx);
+
+    let history = w.history();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].txhash, received.txhash);
+    assert_eq!(history[0].height, BlockHeight(1));
+    assert_eq!(history[0].net_change, [(Denom::Mel, 1_000_000)].into());
+    assert_eq!(history[1].txhash, tx.hash_nosigs());
+    assert_eq!(history[1].height, BlockHeight(2));
+    assert_eq!(
+        history[1].net_change,
+        [(Denom::Mel, -(10_000 + tx.fee.0 as i128))].into()
+    );
+    assert_eq!(history[1].kind, TxKind::Normal);
+}
+
+#[test]
+fn net_change_of_a_transaction_before_sending_it() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 300_000, Denom::Mel),
+            coin(addr, 2, 400_000, Denom::Mel),
+        ],
+    );
+    let tx = w
+        .prepare_tx(send(vec![burn(500_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    let change = tx
+        .outputs
+        .iter()
+        .find(|output| output.covhash == addr)
+        .unwrap()
+        .value;
+    assert_eq!(
+        w.net_change_of(&tx),
+        [(Denom::Mel, -700_000 + change.0 as i128)].into()
+    );
+    assert_eq!(
+        w.net_change_of(&tx),
+        [(Denom::Mel, -(500_000 + tx.fee.0 as i128))].into()
+    );
+}
+
+#[test]
+fn proposer_rewards_are_recognized() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    let reward = CoinID::proposer_reward(BlockHeight(1));
+    let (_, reward_data) = coin(addr, 1, 5_000, Denom::Mel);
+    fund(
+        &mut w,
+        vec![(reward, reward_data), coin(addr, 2, 1_000, Denom::Mel)],
+    );
+    let coinbase: Vec<CoinID> = w.coinbase_coins().map(|(coin, _)| *coin).collect();
+    assert_eq!(coinbase, vec![reward]);
+    let rewards: Vec<_> = w
+        .history()
+        .iter()
+        .filter(|entry| entry.is_proposer_reward())
+        .map(|entry| entry.txhash)
+        .collect();
+    assert_eq!(rewards, vec![reward.txhash]);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use bytes::Bytes;
+use common::*;
+use melstructs::Denom;
+use melwallet::{apply_signer, finalize, PartialError, PartialTransaction, PrepareTxArgs, Signer};
+
+#[test]
+fn partial_transactions_round_trip() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 300_000, Denom::Mel),
+            coin(addr, 2, 400_000, Denom::Mel),
+        ],
+    );
+    let partial = w
+        .export_partial(
+            &send(vec![burn(500_000, Denom::Mel)]),
+            s.sig_size(),
+            s.covenant(),
+            FEE_MULTIPLIER,
+        )
+        .unwrap();
+    assert_eq!(partial.signed, vec![false, false]);
+    assert!(matches!(
+        finalize(partial.clone()),
+        Err(PartialError::Unsigned(0))
+    ));
+
+    // the signing machine only gets the bytes, and has no wallet
+    let bytes = stdcode::serialize(&partial).unwrap();
+    let received: PartialTransaction = stdcode::deserialize(&bytes).unwrap();
+    let signed = apply_signer(received, &s).unwrap();
+    assert_eq!(signed.signed, vec![true, true]);
+    // signers that can't spend the coins leave them alone
+    let untouched = apply_signer(partial, &signer()).unwrap();
+    assert_eq!(untouched.signed, vec![false, false]);
+
+    let tx = finalize(signed).unwrap();
+    w.verify_signed(&tx).unwrap();
+}
+
+#[test]
+fn unsigned_json_round_trips() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let args = PrepareTxArgs {
+        data: Bytes::from_static(&[0xab, 0xcd]),
+        ..send(vec![burn(1_000, Denom::Mel)])
+    };
+    let json = w
+        .unsigned_tx_json(&args, s.sig_size(), s.covenant(), FEE_MULTIPLIER)
+        .unwrap();
+    // bytes are hex strings
+    assert!(json.contains("\"abcd\""));
+
+    let partial = PartialTransaction::from_json(&json).unwrap();
+    let expected = w
+        .export_partial(&args, s.sig_size(), s.covenant(), FEE_MULTIPLIER)
+        .unwrap();
+    assert_eq!(partial.transaction, expected.transaction);
+    assert_eq!(partial.input_coins, expected.input_coins);
+    assert_eq!(partial.to_json(), json);
+
+    let tx = finalize(apply_signer(partial, &s).unwrap()).unwrap();
+    w.verify_signed(&tx).unwrap();
+}
@@ -0,0 +1,252 @@
+mod common;
+
+use common::*;
+use melstructs::{Address, BlockHeight, CoinID, CoinValue, Denom, NetID, Transaction};
+use melwallet::{AddPendingError, PrepareTxArgs, PrepareTxError, ReplaceError, Signer, Wallet};
+
+/// A wallet for the signer holding two MEL coins, with IDs from 1 and 2.
+fn two_coin_wallet(s: &impl Signer) -> Wallet {
+    let mut w = wallet(s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 1_000_000, Denom::Mel),
+        ],
+    );
+    w
+}
+
+/// A transaction spending exactly the given coin of the wallet, paying the given fee.
+fn spend(w: &Wallet, s: &impl Signer, coin: CoinID, fee: u128) -> Transaction {
+    let args = PrepareTxArgs {
+        inputs: vec![(coin, w.confirmed_utxos[&coin].clone())],
+        coin_control: true,
+        fee_override: Some(CoinValue(fee)),
+        ..send(vec![burn(10_000, Denom::Mel)])
+    };
+    w.prepare_tx(args, s, FEE_MULTIPLIER).unwrap()
+}
+
+#[test]
+fn stale_pending_transactions_expire() {
+    let s = signer();
+    let mut w = two_coin_wallet(&s);
+    let tx = spend(&w, &s, coin_id(1), 100_000);
+    let txhash = tx.hash_nosigs();
+    w.add_pending(tx).unwrap();
+    for _ in 0..10 {
+        fund(&mut w, vec![]);
+    }
+    assert!(w.evict_expired_pending(BlockHeight(10)).is_empty());
+    assert!(w.locked_by(&coin_id(1)).is_some());
+    fund(&mut w, vec![]);
+    assert_eq!(w.evict_expired_pending(BlockHeight(10)), vec![txhash]);
+    assert!(w.pending_outgoing.is_empty());
+    assert_eq!(w.spendable_balances(), w.balances());
+}
+
+#[test]
+fn removing_a_pending_transaction_unlocks_its_coins() {
+    let s = signer();
+    let mut w = two_coin_wallet(&s);
+    let first = spend(&w, &s, coin_id(1), 100_000);
+    let second = spend(&w, &s, coin_id(2), 100_000);
+    let first_hash = first.hash_nosigs();
+    w.add_pending(first.clone()).unwrap();
+    w.add_pending(second).unwrap();
+    assert!(w.spendable_balances().is_empty());
+
+    assert_eq!(w.remove_pending(first_hash), Some(first));
+    assert_eq!(w.remove_pending(first_hash), None);
+    assert_eq!(w.pending_inputs(), [coin_id(2)].into());
+    assert_eq!(
+        w.spendable_balances().get(&Denom::Mel).copied(),
+        Some(CoinValue(1_000_000))
+    );
+}
+
+#[test]
+fn conflicting_pending_transactions_are_rejected() {
+    let s = signer();
+    let mut w = two_coin_wallet(&s);
+    let first = spend(&w, &s, coin_id(1), 100_000);
+    let double_spend = spend(&w, &s, coin_id(1), 200_000);
+    let first_hash = first.hash_nosigs();
+    w.add_pending(first).unwrap();
+    let before = w.clone();
+    assert!(matches!(
+        w.add_pending(double_spend),
+        Err(AddPendingError::Conflict(txhash)) if txhash == first_hash
+    ));
+    assert_eq!(w, before);
+}
+
+#[test]
+fn pending_transactions_can_be_replaced_with_a_higher_fee() {
+    let s = signer();
+    let mut w = two_coin_wallet(&s);
+    let old = spend(&w, &s, coin_id(1), 100_000);
+    let cheaper = spend(&w, &s, coin_id(1), 50_000);
+    let elsewhere = spend(&w, &s, coin_id(2), 200_000);
+    let new = spend(&w, &s, coin_id(1), 200_000);
+    let old_hash = old.hash_nosigs();
+    w.add_pending(old).unwrap();
+
+    assert!(matches!(
+        w.replace_pending(old_hash, cheaper),
+        Err(ReplaceError::FeeNotHigher { old, new })
+            if old == CoinValue(100_000) && new == CoinValue(50_000)
+    ));
+    assert!(matches!(
+        w.replace_pending(old_hash, elsewhere),
+        Err(ReplaceError::MissingInput(coin)) if coin == coin_id(1)
+    ));
+
+    let new_hash = new.hash_nosigs();
+    w.replace_pending(old_hash, new).unwrap();
+    assert_eq!(
+        w.pending_outgoing.keys().copied().collect::<Vec<_>>(),
+        vec![new_hash]
+    );
+    assert_eq!(w.locked_by(&coin_id(1)), Some(new_hash));
+}
+
+#[test]
+fn locked_coins_point_at_their_pending_transaction() {
+    let s = signer();
+    let mut w = two_coin_wallet(&s);
+    let tx = spend(&w, &s, coin_id(1), 100_000);
+    let txhash = tx.hash_nosigs();
+    w.add_pending(tx).unwrap();
+    assert_eq!(w.locked_by(&coin_id(1)), Some(txhash));
+    assert_eq!(w.locked_by(&coin_id(2)), None);
+    assert_eq!(w.locked_coins(), [(coin_id(1), txhash)].into());
+}
+
+#[test]
+fn bumping_the_fee_keeps_the_recipients() {
+    let s = signer();
+    let mut w = two_coin_wallet(&s);
+    let old = w
+        .prepare_tx(send(vec![burn(500_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    w.add_pending(old.clone()).unwrap();
+    assert!(matches!(
+        w.bump_fee(&old, old.fee, &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::FeeNotHigher { .. })
+    ));
+
+    let new_fee = old.fee + CoinValue(10_000);
+    let new = w.bump_fee(&old, new_fee, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(new.fee, new_fee);
+    assert!(old.inputs.iter().all(|coin| new.inputs.contains(coin)));
+    let change = |tx: &Transaction| {
+        tx.outputs
+            .iter()
+            .filter(|output| output.covhash == w.address)
+            .map(|output| output.value)
+            .sum::<CoinValue>()
+    };
+    assert_eq!(change(&new) + CoinValue(10_000), change(&old));
+    assert!(new.outputs.contains(&burn(500_000, Denom::Mel)));
+    w.replace_pending(old.hash_nosigs(), new).unwrap();
+}
+
+#[test]
+fn pending_transactions_without_change_clear_when_their_inputs_are_spent() {
+    let s = signer();
+    let mut w = two_coin_wallet(&s);
+    let args = PrepareTxArgs {
+        inputs: vec![(coin_id(1), w.confirmed_utxos[&coin_id(1)].clone())],
+        coin_control: true,
+        fee_override: Some(CoinValue(100_000)),
+        ..send(vec![burn(900_000, Denom::Mel)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert!(tx.outputs.iter().all(|output| output.covhash != w.address));
+    w.add_pending(tx.clone()).unwrap();
+
+    confirm(&mut w, &tx);
+    assert!(w.pending_outgoing.is_empty());
+    assert!(w.locked_by(&coin_id(1)).is_none());
+    assert_eq!(w.spendable_balances(), w.balances());
+}
+
+#[test]
+fn only_old_pending_transactions_are_rebroadcast() {
+    let s = signer();
+    let mut w = two_coin_wallet(&s);
+    let old = spend(&w, &s, coin_id(1), 100_000);
+    let new = spend(&w, &s, coin_id(2), 100_000);
+    w.add_pending(old.clone()).unwrap();
+    w.add_coins_at(NetID::Testnet, BlockHeight(10), vec![], vec![])
+        .unwrap();
+    w.add_pending(new).unwrap();
+    assert!(w.rebroadcast_candidates(BlockHeight(20)).is_empty());
+    assert_eq!(w.rebroadcast_candidates(BlockHeight(5)), vec![&old]);
+}
+
+#[test]
+fn pending_transactions_with_vanished_inputs_are_pruned() {
+    let s = signer();
+    let mut w = two_coin_wallet(&s);
+    w.add_pending(spend(&w, &s, coin_id(1), 100_000)).unwrap();
+    w.add_pending(spend(&w, &s, coin_id(2), 100_000)).unwrap();
+    assert_eq!(w.prune_pending(), 0);
+
+    w.confirmed_utxos.remove(&coin_id(1));
+    assert_eq!(w.prune_pending(), 1);
+    assert_eq!(w.pending_outgoing.len(), 1);
+    assert!(w.locked_by(&coin_id(2)).is_some());
+}
+
+#[test]
+fn pending_inputs_and_outputs_come_from_pending_transactions() {
+    let s = signer();
+    let mut w = two_coin_wallet(&s);
+    assert!(w.pending_inputs().is_empty());
+    assert!(w.pending_outputs().is_empty());
+    let tx = spend(&w, &s, coin_id(1), 100_000);
+    let txhash = tx.hash_nosigs();
+    w.add_pending(tx.clone()).unwrap();
+
+    assert_eq!(w.pending_inputs(), [coin_id(1)].into());
+    let expected: Vec<_> = tx
+        .outputs
+        .iter()
+        .enumerate()
+        .map(|(i, output)| (txhash, i, output.clone()))
+        .collect();
+    assert_eq!(w.pending_outputs(), expected);
+}
+
+#[test]
+fn adding_the_same_pending_transaction_twice_is_a_no_op() {
+    let s = signer();
+    let mut w = two_coin_wallet(&s);
+    let tx = spend(&w, &s, coin_id(1), 100_000);
+    assert!(w.try_add_pending(tx.clone()).unwrap());
+    assert!(!w.try_add_pending(tx).unwrap());
+    assert_eq!(w.pending_outgoing.len(), 1);
+}
+
+#[test]
+fn external_inputs_spent_by_pending_transactions_are_rejected() {
+    let s = signer();
+    let mut w = two_coin_wallet(&s);
+    let vault = Address(tmelcrypt::hash_single(b"vault"));
+    let (external_id, external_coin) = external(vault, 3, 50_000, Denom::Mel);
+    let args = PrepareTxArgs {
+        inputs: vec![(external_id, external_coin)],
+        ..send(vec![burn(10_000, Denom::Mel)])
+    };
+    let tx = w.prepare_tx(args.clone(), &s, FEE_MULTIPLIER).unwrap();
+    assert!(tx.inputs.contains(&external_id));
+    w.add_pending(tx).unwrap();
+    assert!(matches!(
+        w.prepare_tx(args, &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::BadExternalInput(coin)) if coin == external_id
+    ));
+}
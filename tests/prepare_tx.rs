@@ -0,0 +1,739 @@
+mod common;
+
+use bytes::Bytes;
+use common::*;
+use melstructs::{Address, BlockHeight, CoinValue, Denom};
+use melwallet::{
+    encode_locktime, locktime_of, minted_denom, CoinSelectionStrategy, PrepareTxArgs,
+    PrepareTxError, Signer, FEE_DENOM, LOCKTIME_TAG, MAX_FEE_BALLAST_PPM, MAX_SPLIT_WEIGHT,
+};
+
+#[test]
+fn empty_wallet_fails_right_away() {
+    let s = signer();
+    let w = wallet(&s);
+    assert!(matches!(
+        w.prepare_tx(send(vec![burn(10_000, Denom::Mel)]), &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::InsufficientFunds { .. })
+    ));
+}
+
+#[test]
+fn empty_wallet_reports_the_denomination_that_is_short() {
+    let s = signer();
+    let w = wallet(&s);
+    let args = send(vec![
+        burn(1_000, Denom::Sym),
+        burn(10_000, Denom::Mel),
+        burn(2_000, Denom::Sym),
+    ]);
+    assert!(matches!(
+        w.prepare_tx(args, &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::InsufficientFunds {
+            denom: Denom::Sym,
+            needed: CoinValue(3_000),
+            available: CoinValue(0),
+        })
+    ));
+}
+
+#[test]
+fn shortfalls_report_what_is_available() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 500, Denom::Sym),
+        ],
+    );
+    assert!(matches!(
+        w.prepare_tx(send(vec![burn(800, Denom::Sym)]), &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::InsufficientFunds {
+            denom: Denom::Sym,
+            needed: CoinValue(800),
+            available: CoinValue(500),
+        })
+    ));
+}
+
+#[test]
+fn too_low_fee_override_reports_the_fee_needed() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000_000, Denom::Mel)]);
+    let args = PrepareTxArgs {
+        fee_override: Some(CoinValue(1)),
+        ..send(vec![burn(10_000, Denom::Mel)])
+    };
+    assert!(matches!(
+        w.prepare_tx(args, &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::InsufficientFunds {
+            denom: Denom::Mel,
+            needed,
+            available: CoinValue(1),
+        }) if needed > CoinValue(1)
+    ));
+}
+
+#[test]
+fn empty_wallet_still_estimates_weight_of_unfunded_outputs() {
+    let s = signer();
+    let w = wallet(&s);
+    let weight = w
+        .estimate_weight(&send(vec![burn(1_000_000, Denom::NewCustom)]), 64, 100)
+        .unwrap();
+    assert!(weight > 1000);
+}
+
+#[test]
+fn too_many_outputs_exceed_max_weight() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000_000, Denom::Mel)]);
+    let args = PrepareTxArgs {
+        max_weight: Some(20_000),
+        ..send((0..30).map(|_| burn(10_000, Denom::Mel)).collect())
+    };
+    assert!(matches!(
+        w.prepare_tx(args.clone(), &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::TooLarge { weight, limit: 20_000 }) if weight > 20_000
+    ));
+    let args = PrepareTxArgs {
+        max_weight: Some(100_000),
+        ..args
+    };
+    w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+}
+
+#[test]
+fn exactly_enough_mel_for_output_and_fee() {
+    let s = signer();
+    // the fee with a change output is an upper bound on the fee without one
+    let mut rich = wallet(&s);
+    let addr = rich.address;
+    fund(&mut rich, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let fee = rich
+        .prepare_tx(send(vec![burn(10_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap()
+        .fee;
+
+    let mut w = wallet(&s);
+    fund(&mut w, vec![coin(addr, 2, 10_000 + fee.0, Denom::Mel)]);
+    let tx = w
+        .prepare_tx(send(vec![burn(10_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    // the fee search may leave a sliver of change, but all of the MEL is accounted for
+    assert!(tx.outputs.contains(&burn(10_000, Denom::Mel)));
+    let total: u128 = tx.outputs.iter().map(|output| output.value.0).sum();
+    assert_eq!(total + tx.fee.0, 10_000 + fee.0);
+    w.verify_signed(&tx).unwrap();
+}
+
+#[test]
+fn exactly_enough_mel_for_the_fee_alone() {
+    let s = signer();
+    // the fee with a change output is an upper bound on the fee without one
+    let mut rich = wallet(&s);
+    let addr = rich.address;
+    fund(&mut rich, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let fee = rich
+        .prepare_tx(send(vec![]), &s, FEE_MULTIPLIER)
+        .unwrap()
+        .fee;
+
+    let mut w = wallet(&s);
+    fund(&mut w, vec![coin(addr, 2, fee.0, Denom::Mel)]);
+    let tx = w.prepare_tx(send(vec![]), &s, FEE_MULTIPLIER).unwrap();
+    // the fee search may leave a sliver of change, but all of the MEL is accounted for
+    let total: u128 = tx.outputs.iter().map(|output| output.value.0).sum();
+    assert_eq!(total + tx.fee.0, fee.0);
+}
+
+#[test]
+fn extra_covenants_are_included() {
+    let s = signer();
+    let vault = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let vault_address = wallet(&vault).address;
+    let args = PrepareTxArgs {
+        inputs: vec![external(vault_address, 2, 50_000, Denom::Mel)],
+        covenants: vec![vault.covenant(), s.covenant()],
+        ..send(vec![burn(40_000, Denom::Mel)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert!(tx.inputs.contains(&coin_id(2)));
+    // the signer's covenant is only included once
+    assert_eq!(tx.covenants.len(), 2);
+    assert!(tx.covenants.contains(&vault.covenant()));
+    assert!(tx.covenants.contains(&s.covenant()));
+    assert!(tx.fee >= base_fee(&tx));
+}
+
+#[test]
+fn minting_a_token_needs_only_mel_for_the_fee() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let tx = w
+        .prepare_tx(
+            send(vec![burn(1_000_000_000, Denom::NewCustom)]),
+            &s,
+            FEE_MULTIPLIER,
+        )
+        .unwrap();
+    assert_eq!(tx.inputs, vec![coin_id(1)]);
+    assert!(tx.fee > CoinValue(0));
+    assert!(tx.outputs.contains(&burn(1_000_000_000, Denom::NewCustom)));
+    assert_eq!(minted_denom(&tx), Some(Denom::Custom(tx.hash_nosigs())));
+}
+
+#[test]
+fn fee_is_close_to_the_minimum() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 1_000_000, Denom::Mel),
+            coin(addr, 3, 500, Denom::Sym),
+        ],
+    );
+    let tx = w
+        .prepare_tx(
+            send(vec![burn(1_500_000, Denom::Mel), burn(300, Denom::Sym)]),
+            &s,
+            FEE_MULTIPLIER,
+        )
+        .unwrap();
+    let minimum = base_fee(&tx);
+    assert!(tx.fee >= minimum);
+    assert!(tx.fee.0 - minimum.0 <= minimum.0 / 100);
+}
+
+#[test]
+fn bad_external_inputs_are_rejected() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let other = wallet(&signer()).address;
+    // a coin of the wallet itself
+    let args = PrepareTxArgs {
+        inputs: vec![external(addr, 1, 1_000_000, Denom::Mel)],
+        ..send(vec![burn(10_000, Denom::Mel)])
+    };
+    assert!(matches!(
+        w.prepare_tx(args, &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::BadExternalInput(id)) if id == coin_id(1)
+    ));
+    // a coin worth nothing
+    let args = PrepareTxArgs {
+        inputs: vec![external(other, 2, 0, Denom::Mel)],
+        ..send(vec![burn(10_000, Denom::Mel)])
+    };
+    assert!(matches!(
+        w.prepare_tx(args, &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::BadExternalInput(id)) if id == coin_id(2)
+    ));
+}
+
+#[test]
+fn fee_override_is_paid_exactly() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let args = PrepareTxArgs {
+        fee_override: Some(CoinValue(50_000)),
+        ..send(vec![burn(10_000, Denom::Mel)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(tx.fee, CoinValue(50_000));
+    assert!(tx.fee > base_fee(&tx));
+    // but not if the wallet can't afford it
+    let args = PrepareTxArgs {
+        fee_override: Some(CoinValue(2_000_000)),
+        ..send(vec![burn(10_000, Denom::Mel)])
+    };
+    assert!(matches!(
+        w.prepare_tx(args, &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::InsufficientFunds {
+            denom: Denom::Mel,
+            available: CoinValue(1_000_000),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn max_fee_caps_the_fee() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let fee = w
+        .prepare_tx(send(vec![burn(10_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap()
+        .fee;
+    let args = PrepareTxArgs {
+        max_fee: Some(CoinValue(fee.0 - 1)),
+        ..send(vec![burn(10_000, Denom::Mel)])
+    };
+    assert!(matches!(
+        w.prepare_tx(args, &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::FeeTooHigh { needed, cap })
+            if needed == fee && cap == CoinValue(fee.0 - 1)
+    ));
+    let args = PrepareTxArgs {
+        max_fee: Some(fee),
+        ..send(vec![burn(10_000, Denom::Mel)])
+    };
+    assert_eq!(w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap().fee, fee);
+}
+
+#[test]
+fn transactions_are_canonically_ordered() {
+    let s = signer();
+    let addr = wallet(&s).address;
+    let coins = vec![
+        coin(addr, 1, 300_000, Denom::Mel),
+        coin(addr, 2, 400_000, Denom::Mel),
+        coin(addr, 3, 500, Denom::Sym),
+    ];
+    let outputs = vec![burn(500_000, Denom::Mel), burn(200, Denom::Sym)];
+
+    let mut a = wallet(&s);
+    fund(&mut a, coins.clone());
+    let mut b = wallet(&s);
+    fund(&mut b, coins.into_iter().rev().collect());
+    let tx_a = a
+        .prepare_tx(send(outputs.clone()), &s, FEE_MULTIPLIER)
+        .unwrap();
+    let tx_b = b
+        .prepare_tx(
+            send(outputs.into_iter().rev().collect()),
+            &s,
+            FEE_MULTIPLIER,
+        )
+        .unwrap();
+    assert_eq!(
+        stdcode::serialize(&tx_a).unwrap(),
+        stdcode::serialize(&tx_b).unwrap()
+    );
+    assert!(tx_a.inputs.is_sorted());
+    assert!(tx_a
+        .outputs
+        .is_sorted_by_key(|output| (output.denom, output.value, output.covhash)));
+    a.verify_signed(&tx_a).unwrap();
+}
+
+#[test]
+fn simulation_matches_the_signed_transaction() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 300_000, Denom::Mel),
+            coin(addr, 2, 400_000, Denom::Mel),
+        ],
+    );
+    let args = send(vec![burn(500_000, Denom::Mel)]);
+    let preview = w
+        .simulate_tx(&args, s.sig_size(), s.covenant(), FEE_MULTIPLIER)
+        .unwrap();
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(preview.fee, tx.fee);
+    assert_eq!(preview.outputs, tx.outputs);
+    assert_eq!(preview.touched_coin_count, 2);
+    assert_eq!(
+        preview.inputs.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        tx.inputs
+    );
+    assert!(preview
+        .transaction
+        .sigs
+        .iter()
+        .all(|sig| sig.iter().all(|b| *b == 0)));
+}
+
+#[test]
+fn dust_change_goes_to_the_fee() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let max = w.max_spendable(Denom::Mel, &s, FEE_MULTIPLIER);
+    let sent = max.0 - 500;
+    let tx = w
+        .prepare_tx(send(vec![burn(sent, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    assert_eq!(tx.outputs.len(), 1);
+    assert_eq!(tx.fee.0 + sent, 1_000_000);
+}
+
+#[test]
+fn dust_outputs_are_rejected() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    assert!(matches!(
+        w.prepare_tx(send(vec![burn(999, Denom::Mel)]), &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::DustOutput(CoinValue(999)))
+    ));
+    // other tokens can't pay fees, so they have no dust
+    fund(&mut w, vec![coin(addr, 2, 5, Denom::Sym)]);
+    w.prepare_tx(send(vec![burn(1, Denom::Sym)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+}
+
+#[test]
+fn dust_inputs_are_picked_last() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 500, Denom::Mel),
+            coin(addr, 2, 1_000_000, Denom::Mel),
+        ],
+    );
+    let args = PrepareTxArgs {
+        coin_selection: CoinSelectionStrategy::SmallestFirst,
+        ..send(vec![burn(100_000, Denom::Mel)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(tx.inputs, vec![coin_id(2)]);
+}
+
+#[test]
+fn unsigned_transactions_only_lack_signatures() {
+    let s = signer();
+    // a watch-only copy, which only knows the address
+    let mut watcher = wallet(&s);
+    let addr = watcher.address;
+    fund(
+        &mut watcher,
+        vec![
+            coin(addr, 1, 300_000, Denom::Mel),
+            coin(addr, 2, 400_000, Denom::Mel),
+        ],
+    );
+    let args = send(vec![burn(500_000, Denom::Mel)]);
+    let unsigned = watcher
+        .prepare_unsigned(&args, s.sig_size(), s.covenant(), FEE_MULTIPLIER)
+        .unwrap();
+    assert_eq!(unsigned.sigs, vec![vec![0u8; 64]; 2]);
+    let signed = watcher.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    let mut resigned = unsigned.clone();
+    for i in 0..resigned.inputs.len() {
+        resigned.sigs[i] = s.sign_detached(&unsigned, i).unwrap();
+    }
+    assert_eq!(
+        stdcode::serialize(&resigned).unwrap(),
+        stdcode::serialize(&signed).unwrap()
+    );
+}
+
+#[test]
+fn fee_estimates_match_the_prepared_fee() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 300_000, Denom::Mel),
+            coin(addr, 2, 400_000, Denom::Mel),
+            coin(addr, 3, 500, Denom::Sym),
+        ],
+    );
+    for args in [
+        send(vec![burn(100_000, Denom::Mel)]),
+        send(vec![burn(500_000, Denom::Mel), burn(100, Denom::Sym)]),
+    ] {
+        let estimate = w
+            .estimate_fee(&args, s.sig_size(), s.covenant().len(), FEE_MULTIPLIER)
+            .unwrap();
+        let fee = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap().fee;
+        // the estimate leaves out the cost of running the covenant, which is small, but not negligible
+        assert!(estimate <= fee);
+        assert!(fee.0 - estimate.0 <= fee.0 / 4);
+    }
+}
+
+#[test]
+fn locktimes_are_encoded_in_the_data() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let args = PrepareTxArgs {
+        locktime: Some(BlockHeight(1234)),
+        data: Bytes::from_static(b"memo"),
+        ..send(vec![burn(10_000, Denom::Mel)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(tx.data, encode_locktime(BlockHeight(1234), b"memo"));
+    assert_eq!(&tx.data[..8], LOCKTIME_TAG);
+    assert_eq!(tx.data[8..16], 1234u64.to_be_bytes());
+    assert_eq!(&tx.data[16..], b"memo");
+    assert_eq!(locktime_of(&tx), Some(BlockHeight(1234)));
+}
+
+#[test]
+fn splits_fan_out_equal_coins() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 100_000_000, Denom::Mel)]);
+    let tx = w
+        .prepare_split(
+            Address::coin_destroy(),
+            Denom::Mel,
+            CoinValue(10_000),
+            20,
+            &s,
+            FEE_MULTIPLIER,
+        )
+        .unwrap();
+    let pieces: Vec<_> = tx
+        .outputs
+        .iter()
+        .filter(|output| output.covhash == Address::coin_destroy())
+        .collect();
+    assert_eq!(pieces.len(), 20);
+    assert!(pieces.iter().all(|piece| piece.value == CoinValue(10_000)));
+    let change: Vec<_> = tx
+        .outputs
+        .iter()
+        .filter(|output| output.covhash == addr)
+        .collect();
+    assert_eq!(change.len(), 1);
+    assert_eq!(change[0].value, CoinValue(100_000_000 - 200_000) - tx.fee);
+
+    assert!(matches!(
+        w.prepare_split(
+            Address::coin_destroy(),
+            Denom::Mel,
+            CoinValue(10_000),
+            1000,
+            &s,
+            FEE_MULTIPLIER,
+        ),
+        Err(PrepareTxError::TooLarge { weight, limit: MAX_SPLIT_WEIGHT })
+            if weight > MAX_SPLIT_WEIGHT
+    ));
+}
+
+#[test]
+fn weight_changes_monotonically_with_inputs() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 100_000_000, Denom::Mel)]);
+    let mut args = send(vec![burn(1_000, Denom::Mel); 10]);
+    let mut last = w.estimate_weight(&args, 64, 40).unwrap();
+    // every input is discounted, so each one makes the transaction lighter
+    for i in 2..6 {
+        args.inputs.push(external(addr, i, 1_000, Denom::Mel));
+        let weight = w.estimate_weight(&args, 64, 40).unwrap();
+        assert!(weight < last);
+        last = weight;
+    }
+}
+
+#[test]
+fn unlock_args_fill_the_slots_of_foreign_coins() {
+    use melvm::opcode::OpCode;
+
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let preimage = Bytes::from_static(b"open sesame");
+    // a hashlock, unlocked by the preimage of the hash in the spending input's slot
+    let lock = melvm::Covenant::from_ops(&[
+        OpCode::LoadImm(9),
+        OpCode::PushI(6u32.into()),
+        OpCode::LoadImm(0),
+        OpCode::VRef,
+        OpCode::VRef,
+        OpCode::Hash(32),
+        OpCode::BtoI,
+        OpCode::PushB(tmelcrypt::hash_single(&preimage).0.to_vec()),
+        OpCode::BtoI,
+        OpCode::Eql,
+    ]);
+    let (locked_id, locked) = external(lock.hash(), 2, 5_000, Denom::Mel);
+    let args = PrepareTxArgs {
+        inputs: vec![(locked_id, locked)],
+        input_unlock_args: [(locked_id, preimage.clone())].into_iter().collect(),
+        covenants: vec![lock.to_bytes()],
+        ..send(vec![burn(100_000, Denom::Mel)])
+    };
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    let locked_slot = tx.inputs.iter().position(|id| *id == locked_id).unwrap();
+    assert_eq!(tx.sigs[locked_slot], preimage);
+    assert!(tx.covenants.contains(&lock.to_bytes()));
+    // the wallet's own coin is still signed by the signer
+    w.verify_signed(&tx).unwrap();
+}
+
+#[test]
+fn coin_control_spends_only_the_given_inputs() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 50_000, Denom::Mel),
+            coin(addr, 2, 10_000_000, Denom::Mel),
+        ],
+    );
+    let mut args = PrepareTxArgs {
+        inputs: vec![(coin_id(1), w.confirmed_utxos[&coin_id(1)].clone())],
+        coin_control: true,
+        ..send(vec![burn(100_000, Denom::Mel)])
+    };
+    assert!(matches!(
+        w.prepare_tx(args.clone(), &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::InsufficientFunds {
+            denom: Denom::Mel,
+            ..
+        })
+    ));
+
+    args.inputs = vec![(coin_id(2), w.confirmed_utxos[&coin_id(2)].clone())];
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(tx.inputs, vec![coin_id(2)]);
+    w.verify_signed(&tx).unwrap();
+}
+
+#[test]
+fn token_outputs_pay_the_fee_in_mel() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 5_000, Denom::Sym),
+        ],
+    );
+    let tx = w
+        .prepare_tx(send(vec![burn(2_000, Denom::Sym)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    let mut inputs = tx.inputs.clone();
+    inputs.sort();
+    let mut expected = vec![coin_id(1), coin_id(2)];
+    expected.sort();
+    assert_eq!(inputs, expected);
+    let change = |denom| {
+        tx.outputs
+            .iter()
+            .filter(|output| output.covhash == addr && output.denom == denom)
+            .map(|output| output.value)
+            .sum::<CoinValue>()
+    };
+    assert_eq!(change(Denom::Sym), CoinValue(3_000));
+    assert_eq!(change(Denom::Mel) + tx.fee, CoinValue(1_000_000));
+
+    assert!(matches!(
+        w.prepare_tx(send(vec![burn(6_000, Denom::Sym)]), &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::InsufficientFunds {
+            denom: Denom::Sym,
+            ..
+        })
+    ));
+
+    // without MEL, there's nothing to pay the fee with
+    let mut sym_only = wallet(&s);
+    fund(&mut sym_only, vec![coin(addr, 2, 5_000, Denom::Sym)]);
+    assert!(matches!(
+        sym_only.prepare_tx(send(vec![burn(2_000, Denom::Sym)]), &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::InsufficientFunds {
+            denom: Denom::Mel,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn fees_are_paid_in_the_fee_denomination() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, FEE_DENOM),
+            coin(addr, 2, 5_000, Denom::Sym),
+        ],
+    );
+    let tx = w
+        .prepare_tx(send(vec![burn(2_000, Denom::Sym)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    let net_change = w.net_change_of(&tx);
+    assert_eq!(net_change[&FEE_DENOM], -(tx.fee.0 as i128));
+    assert_eq!(net_change[&Denom::Sym], -2_000);
+}
+
+#[test]
+fn zero_outputs_are_rejected() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    assert!(matches!(
+        w.prepare_tx(
+            send(vec![burn(10_000, Denom::Mel), burn(0, Denom::Sym)]),
+            &s,
+            FEE_MULTIPLIER
+        ),
+        Err(PrepareTxError::ZeroOutput(1))
+    ));
+    assert!(matches!(
+        w.prepare_tx(send(vec![burn(0, Denom::Mel)]), &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::ZeroOutput(0))
+    ));
+    // zero-value mints are allowed as markers
+    w.prepare_tx(send(vec![burn(0, Denom::NewCustom)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+}
+
+#[test]
+fn fractional_ballast_scales_the_fee() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000_000, Denom::Mel)]);
+    let fee = |ppm| {
+        let args = PrepareTxArgs {
+            fee_ballast_ppm: ppm,
+            ..send(vec![burn(10_000, Denom::Mel)])
+        };
+        // a higher multiplier, so that rounding doesn't hide the ballast
+        w.prepare_tx(args, &s, FEE_MULTIPLIER << 4).unwrap().fee.0 as f64
+    };
+    let ratio = fee(100_000) / fee(0);
+    assert!((1.08..1.12).contains(&ratio));
+    // too large fractions are capped
+    assert_eq!(fee(50 * MAX_FEE_BALLAST_PPM), fee(MAX_FEE_BALLAST_PPM));
+}
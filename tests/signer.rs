@@ -0,0 +1,405 @@
+mod common;
+
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use common::*;
+use melstructs::{CoinDataHeight, Denom, Transaction};
+use melwallet::{
+    combine_partials, AsyncSigner, CachingSigner, MultiSigner, PrepareTxArgs, PrepareTxError,
+    Signer, StdEd25519Signer, ThresholdError, ThresholdSigner, VerifyError, Wallet,
+};
+
+#[test]
+fn async_prepare_checks_the_signer() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let args = PrepareTxArgs {
+        check_signer: true,
+        ..send(vec![burn(10_000, Denom::Mel)])
+    };
+    assert!(matches!(
+        block_on(w.prepare_tx_async(args.clone(), &signer(), FEE_MULTIPLIER)),
+        Err(PrepareTxError::SignerMismatch)
+    ));
+    block_on(w.prepare_tx_async(args, &s, FEE_MULTIPLIER)).unwrap();
+}
+
+/// Records the coins it's asked to sign for, and signs with the wrapped signer.
+struct RecordingSigner {
+    inner: StdEd25519Signer,
+    seen: Mutex<Vec<(usize, CoinDataHeight)>>,
+}
+
+impl Signer for RecordingSigner {
+    type Error = Infallible;
+
+    fn covenant(&self) -> Bytes {
+        self.inner.covenant()
+    }
+
+    fn sig_size(&self) -> usize {
+        self.inner.sig_size()
+    }
+
+    fn sign(&self, txn: &Transaction, for_input: usize) -> Result<Transaction, Self::Error> {
+        self.inner.sign(txn, for_input)
+    }
+
+    fn sign_with_context(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+        spent: &CoinDataHeight,
+    ) -> Result<Transaction, Self::Error> {
+        self.seen.lock().unwrap().push((for_input, spent.clone()));
+        self.inner.sign(txn, for_input)
+    }
+
+    fn sign_detached_with_context(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+        spent: &CoinDataHeight,
+    ) -> Result<Bytes, Self::Error> {
+        self.seen.lock().unwrap().push((for_input, spent.clone()));
+        self.inner.sign_detached(txn, for_input)
+    }
+}
+
+/// Like [RecordingSigner], but only asynchronous.
+struct AsyncRecordingSigner(RecordingSigner);
+
+impl AsyncSigner for AsyncRecordingSigner {
+    type Error = Infallible;
+
+    fn async_covenant(&self) -> Bytes {
+        self.0.covenant()
+    }
+
+    fn async_sig_size(&self) -> usize {
+        self.0.sig_size()
+    }
+
+    async fn async_sign(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+    ) -> Result<Transaction, Self::Error> {
+        self.0.sign(txn, for_input)
+    }
+
+    async fn async_sign_with_context(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+        spent: &CoinDataHeight,
+    ) -> Result<Transaction, Self::Error> {
+        self.0.sign_with_context(txn, for_input, spent)
+    }
+}
+
+fn recording_signer() -> RecordingSigner {
+    RecordingSigner {
+        inner: signer(),
+        seen: Mutex::new(vec![]),
+    }
+}
+
+/// A wallet for the signer holding a MEL coin and a SYM coin, and arguments for sending SYM, which spends both.
+fn two_coin_wallet(s: &impl Signer) -> (Wallet, PrepareTxArgs) {
+    let mut w = wallet(s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 500, Denom::Sym),
+        ],
+    );
+    (w, send(vec![burn(100, Denom::Sym)]))
+}
+
+/// Checks that the signer saw exactly the coins each input of the transaction spends.
+fn assert_saw_spent_coins(w: &Wallet, tx: &Transaction, seen: &[(usize, CoinDataHeight)]) {
+    assert_eq!(seen.len(), tx.inputs.len());
+    for (i, cdh) in seen {
+        assert_eq!(&w.confirmed_utxos[&tx.inputs[*i]], cdh);
+    }
+}
+
+#[test]
+fn signers_get_the_spent_coins() {
+    let s = recording_signer();
+    let (w, args) = two_coin_wallet(&s);
+    let tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    assert_saw_spent_coins(&w, &tx, &s.seen.lock().unwrap());
+}
+
+#[test]
+fn async_signers_get_the_spent_coins() {
+    let s = AsyncRecordingSigner(recording_signer());
+    let (w, args) = two_coin_wallet(&s.0);
+    let tx = block_on(w.prepare_tx_async(args, &s, FEE_MULTIPLIER)).unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    assert_saw_spent_coins(&w, &tx, &s.0.seen.lock().unwrap());
+}
+
+/// The receiving end of a one-shot channel, as a future.
+struct Reply<T>(mpsc::Receiver<T>);
+
+impl<T> Future for Reply<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        match self.0.try_recv() {
+            Ok(value) => Poll::Ready(value),
+            Err(_) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Signs on another thread, like a remote signing service, and awaits the reply.
+struct RemoteSigner(Arc<StdEd25519Signer>);
+
+impl AsyncSigner for RemoteSigner {
+    type Error = Infallible;
+
+    fn async_covenant(&self) -> Bytes {
+        self.0.covenant()
+    }
+
+    fn async_sig_size(&self) -> usize {
+        self.0.sig_size()
+    }
+
+    async fn async_sign(
+        &self,
+        txn: &Transaction,
+        for_input: usize,
+    ) -> Result<Transaction, Self::Error> {
+        let (send, recv) = mpsc::channel();
+        let (signer, txn) = (self.0.clone(), txn.clone());
+        std::thread::spawn(move || {
+            let _ = send.send(signer.sign(&txn, for_input));
+        });
+        Reply(recv).await
+    }
+}
+
+#[test]
+fn async_signers_sign_every_input() {
+    let s = RemoteSigner(Arc::new(signer()));
+    let (w, args) = two_coin_wallet(s.0.as_ref());
+    let tx = block_on(w.prepare_tx_async(args, &s, FEE_MULTIPLIER)).unwrap();
+    assert_eq!(tx.inputs.len(), 2);
+    assert_eq!(tx.sigs.len(), 2);
+    w.verify_signed(&tx).unwrap();
+}
+
+#[test]
+fn multisig_members_sign_their_own_slots() {
+    let (a, b) = (
+        tmelcrypt::Ed25519SK::generate(),
+        tmelcrypt::Ed25519SK::generate(),
+    );
+    let ms = MultiSigner::std_ed25519(vec![a, b]);
+    assert_eq!(ms.sig_size(), 128);
+    let (w, args) = two_coin_wallet(&ms);
+    let mut tx = w.prepare_tx(args, &ms, FEE_MULTIPLIER).unwrap();
+    for (i, sig) in tx.sigs.iter().enumerate() {
+        assert_eq!(
+            sig[..64],
+            StdEd25519Signer(a).sign_detached(&tx, i).unwrap()
+        );
+        assert_eq!(
+            sig[64..],
+            StdEd25519Signer(b).sign_detached(&tx, i).unwrap()
+        );
+    }
+    w.verify_signed(&tx).unwrap();
+
+    // the covenant checks each signature at its own offset
+    let mut swapped = tx.sigs[1][64..].to_vec();
+    swapped.extend_from_slice(&tx.sigs[1][..64]);
+    tx.sigs[1] = swapped.into();
+    assert!(matches!(
+        w.verify_signed(&tx),
+        Err(VerifyError::Rejected(1))
+    ));
+}
+
+#[test]
+fn threshold_signatures_assemble_from_partials() {
+    let keys: Vec<_> = (0..3).map(|_| tmelcrypt::Ed25519SK::generate()).collect();
+    let publics: Vec<_> = keys.iter().map(|key| key.to_public()).collect();
+    let mut ts = ThresholdSigner::std_ed25519(2, &publics);
+    let (w, args) = two_coin_wallet(&ts);
+    let tx = w
+        .prepare_unsigned(&args, ts.sig_size(), ts.covenant(), FEE_MULTIPLIER)
+        .unwrap();
+    // members sign on their own, without seeing each other's signatures
+    let txhash = tx.hash_nosigs();
+    let partial = |i: usize| Bytes::from(keys[i].sign(&txhash.0));
+
+    ts.add_partial(0, partial(0)).unwrap();
+    assert!(matches!(
+        ts.finalize(tx.clone()),
+        Err(ThresholdError::NotEnoughPartials { have: 1, need: 2 })
+    ));
+    assert!(matches!(
+        ts.add_partial(3, partial(0)),
+        Err(ThresholdError::BadIndex(3))
+    ));
+    ts.add_partial(2, partial(2)).unwrap();
+    let signed = ts.finalize(tx).unwrap();
+    w.verify_signed(&signed).unwrap();
+    let partials = [(0, partial(0)), (2, partial(2))].into();
+    assert_eq!(signed.sigs[0], combine_partials(&partials, 3, 64));
+}
+
+/// Checks that the signer's detached signature of each input is what signing puts in that slot.
+fn assert_detached_matches_sign(s: &impl Signer, tx: &Transaction) {
+    for i in 0..tx.inputs.len() {
+        assert_eq!(
+            s.sign_detached(tx, i).unwrap(),
+            s.sign(tx, i).unwrap().sigs[i]
+        );
+    }
+}
+
+#[test]
+fn detached_signatures_match_signing() {
+    let s = recording_signer();
+    let (w, args) = two_coin_wallet(&s);
+    let tx = w
+        .prepare_unsigned(&args, s.sig_size(), s.covenant(), FEE_MULTIPLIER)
+        .unwrap();
+    // the recording signer relies on the default sign_detached
+    assert_detached_matches_sign(&s, &tx);
+    assert_detached_matches_sign(&s.inner, &tx);
+    assert_detached_matches_sign(
+        &MultiSigner::std_ed25519(vec![tmelcrypt::Ed25519SK::generate(); 2]),
+        &tx,
+    );
+}
+
+#[test]
+fn ed25519_signatures_round_trip() {
+    let s = signer();
+    let (w, args) = two_coin_wallet(&s);
+    let mut tx = w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+    w.verify_signed(&tx).unwrap();
+    tx.sigs[0] = signer().sign_detached(&tx, 0).unwrap();
+    assert!(matches!(
+        w.verify_signed(&tx),
+        Err(VerifyError::Rejected(0))
+    ));
+}
+
+#[test]
+fn ed25519_signers_identify_with_their_public_key() {
+    let sk = tmelcrypt::Ed25519SK::generate();
+    let pk = sk.to_public();
+    let s = StdEd25519Signer(sk);
+    assert_eq!(s.public_identity(), Some(Bytes::copy_from_slice(&pk.0)));
+    // a multisig has no single key to show
+    let multisig = MultiSigner::std_ed25519(vec![tmelcrypt::Ed25519SK::generate()]);
+    assert_eq!(multisig.public_identity(), None);
+}
+
+#[test]
+fn prepare_checks_the_signer() {
+    let s = signer();
+    let other = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    assert!(w.matches_signer(&s));
+    assert!(!w.matches_signer(&other));
+    let args = PrepareTxArgs {
+        check_signer: true,
+        ..send(vec![burn(10_000, Denom::Mel)])
+    };
+    assert!(matches!(
+        w.prepare_tx(args.clone(), &other, FEE_MULTIPLIER),
+        Err(PrepareTxError::SignerMismatch)
+    ));
+    w.prepare_tx(args, &s, FEE_MULTIPLIER).unwrap();
+}
+
+#[test]
+fn parallel_signing_matches_serial_signing() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        (0..200)
+            .map(|i| coin(addr, i, 10_000, Denom::Mel))
+            .collect(),
+    );
+    let args = send(vec![burn(1_999_000, Denom::Mel)]);
+    let serial = w.prepare_tx(args.clone(), &s, FEE_MULTIPLIER).unwrap();
+    let parallel = w.prepare_tx_parallel(args, &s, FEE_MULTIPLIER).unwrap();
+    assert_eq!(serial.inputs.len(), 200);
+    assert_eq!(serial, parallel);
+}
+
+/// Counts how often it's asked for its covenant, and signs with the wrapped signer.
+struct CountingSigner {
+    inner: StdEd25519Signer,
+    covenants: AtomicUsize,
+}
+
+impl Signer for CountingSigner {
+    type Error = Infallible;
+
+    fn covenant(&self) -> Bytes {
+        self.covenants.fetch_add(1, Ordering::SeqCst);
+        self.inner.covenant()
+    }
+
+    fn sig_size(&self) -> usize {
+        self.inner.sig_size()
+    }
+
+    fn sign(&self, txn: &Transaction, for_input: usize) -> Result<Transaction, Self::Error> {
+        self.inner.sign(txn, for_input)
+    }
+}
+
+#[test]
+fn caching_signers_compute_the_covenant_once() {
+    let inner = signer();
+    let mut w = wallet(&inner);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let s = CachingSigner::new(CountingSigner {
+        inner,
+        covenants: AtomicUsize::new(0),
+    });
+    let args = PrepareTxArgs {
+        check_signer: true,
+        ..send(vec![burn(1_000, Denom::Mel)])
+    };
+    for _ in 0..3 {
+        w.prepare_tx(args.clone(), &s, FEE_MULTIPLIER).unwrap();
+    }
+    assert_eq!(s.into_inner().covenants.load(Ordering::SeqCst), 1);
+}
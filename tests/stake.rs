@@ -0,0 +1,36 @@
+mod common;
+
+use common::*;
+use melstructs::{CoinValue, Denom, StakeDoc, TxKind};
+
+#[test]
+fn stakes_lock_syms_with_the_stake_document() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 1_000_000, Denom::Sym),
+        ],
+    );
+    let doc = StakeDoc {
+        pubkey: s.0.to_public(),
+        e_start: 3,
+        e_post_end: 10,
+        syms_staked: CoinValue(0),
+    };
+    let tx = w
+        .prepare_stake(CoinValue(600_000), doc, &s, FEE_MULTIPLIER)
+        .unwrap();
+    assert_eq!(tx.kind, TxKind::Stake);
+    assert_eq!(tx.outputs[0].covhash, addr);
+    assert_eq!(tx.outputs[0].denom, Denom::Sym);
+    assert_eq!(tx.outputs[0].value, CoinValue(600_000));
+    let decoded: StakeDoc = stdcode::deserialize(&tx.data).unwrap();
+    assert_eq!(decoded.pubkey, s.0.to_public());
+    assert_eq!((decoded.e_start, decoded.e_post_end), (3, 10));
+    assert_eq!(decoded.syms_staked, CoinValue(600_000));
+    w.verify_signed(&tx).unwrap();
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::*;
+use melstructs::{BlockHeight, CoinValue, Denom};
+use melwallet::DenomStats;
+
+#[test]
+fn stats_summarize_each_denomination() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![]);
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 300, Denom::Mel),
+            coin(addr, 2, 100, Denom::Mel),
+        ],
+    );
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 3, 500, Denom::Mel),
+            coin(addr, 4, 200, Denom::Mel),
+            coin(addr, 5, 7, Denom::Sym),
+        ],
+    );
+    let stats = w.utxo_stats();
+    assert_eq!(stats.len(), 2);
+    assert_eq!(
+        stats[&Denom::Mel],
+        DenomStats {
+            count: 4,
+            total: CoinValue(1100),
+            smallest: CoinValue(100),
+            median: CoinValue(200),
+            largest: CoinValue(500),
+            oldest_height: BlockHeight(2),
+        }
+    );
+    assert_eq!(
+        stats[&Denom::Sym],
+        DenomStats {
+            count: 1,
+            total: CoinValue(7),
+            smallest: CoinValue(7),
+            median: CoinValue(7),
+            largest: CoinValue(7),
+            oldest_height: BlockHeight(3),
+        }
+    );
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use common::*;
+use melstructs::{CoinValue, Denom};
+use melwallet::PrepareTxError;
+
+#[test]
+fn sweeps_move_everything_to_the_wallet() {
+    let s = signer();
+    let paper = signer();
+    let w = wallet(&s);
+    let mut paper_wallet = wallet(&paper);
+    let paper_addr = paper_wallet.address;
+    let coins = vec![
+        external(paper_addr, 1, 300_000, Denom::Mel),
+        external(paper_addr, 2, 700_000, Denom::Mel),
+    ];
+    let tx = w
+        .prepare_sweep(coins.clone(), &paper, FEE_MULTIPLIER)
+        .unwrap();
+    assert_eq!(tx.outputs.len(), 1);
+    assert_eq!(tx.outputs[0].covhash, w.address);
+    assert_eq!(tx.outputs[0].value + tx.fee, CoinValue(1_000_000));
+    assert!(tx.fee >= base_fee(&tx));
+
+    fund(
+        &mut paper_wallet,
+        coins
+            .iter()
+            .map(|(id, cdh)| (*id, cdh.coin_data.clone()))
+            .collect(),
+    );
+    paper_wallet.verify_signed(&tx).unwrap();
+
+    // the coins must belong to the signer being swept
+    assert!(matches!(
+        w.prepare_sweep(coins, &s, FEE_MULTIPLIER),
+        Err(PrepareTxError::BadExternalInput(_))
+    ));
+}
@@ -0,0 +1,352 @@
+mod common;
+
+use common::*;
+use melstructs::{BlockHeight, CoinValue, Denom, NetID};
+use melwallet::{AddCoinsError, AddCoinsOutcome, MergeError, RollbackError, MAX_ROLLBACK_DEPTH};
+
+#[test]
+fn ranges_skip_coins_created_and_spent_within_them() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    let diffs = vec![
+        (
+            BlockHeight(1),
+            vec![
+                coin(addr, 1, 5000, Denom::Mel),
+                coin(addr, 2, 6000, Denom::Mel),
+            ],
+            vec![],
+        ),
+        (BlockHeight(2), vec![], vec![coin_id(1)]),
+        (
+            BlockHeight(3),
+            vec![coin(addr, 3, 7000, Denom::Mel)],
+            vec![],
+        ),
+    ];
+    let mut one_by_one = w.clone();
+    for (height, new_coins, spent_coins) in diffs.clone() {
+        one_by_one
+            .add_coins(NetID::Testnet, height, new_coins, spent_coins)
+            .unwrap();
+    }
+    let outcome = w.add_coins_range(NetID::Testnet, diffs).unwrap();
+    assert!(!outcome.received.contains(&coin_id(1)));
+    assert!(outcome.spent.is_empty());
+    assert_eq!(w.height, BlockHeight(3));
+    assert_eq!(w.confirmed_utxos, one_by_one.confirmed_utxos);
+    assert!(!w.confirmed_utxos.contains_key(&coin_id(1)));
+}
+
+#[test]
+fn rollback_into_a_range_fails() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    let diffs = (1..=10).map(|h| {
+        let coins = match h {
+            1 => vec![coin(addr, 1, 5000, Denom::Mel)],
+            8 => vec![coin(addr, 8, 5000, Denom::Mel)],
+            _ => vec![],
+        };
+        (BlockHeight(h), coins, vec![])
+    });
+    w.add_coins_range(NetID::Testnet, diffs).unwrap();
+    let before = w.clone();
+    assert!(matches!(
+        w.rollback_to(BlockHeight(5)),
+        Err(RollbackError::InsideRange { from, to }) if from == BlockHeight(1) && to == BlockHeight(10)
+    ));
+    assert_eq!(w, before);
+    w.rollback_to(BlockHeight(0)).unwrap();
+    assert!(w.confirmed_utxos.is_empty());
+    assert!(w.history.is_empty());
+}
+
+#[test]
+fn merge_refuses_shards_at_different_heights() {
+    let s = signer();
+    let mut a = wallet(&s);
+    let addr = a.address;
+    let (c, _) = coin(addr, 1, 5000, Denom::Mel);
+    fund(&mut a, vec![coin(addr, 1, 5000, Denom::Mel)]);
+    // a shard of the next block, which spends the coin it never saw
+    let mut b = wallet(&s);
+    b.add_coins_at(NetID::Testnet, BlockHeight(2), vec![], vec![c])
+        .unwrap();
+    let before = a.clone();
+    assert!(matches!(
+        a.merge(&b),
+        Err(MergeError::DifferentHeight { ours, theirs })
+            if ours == BlockHeight(1) && theirs == BlockHeight(2)
+    ));
+    assert_eq!(a, before);
+}
+
+#[test]
+fn merge_combines_denomination_shards() {
+    let s = signer();
+    let addr = wallet(&s).address;
+    let shard = |denom| {
+        let mut w = wallet(&s);
+        w.tracked_denoms = Some([denom].into());
+        fund(
+            &mut w,
+            vec![
+                coin(addr, 1, 5000, Denom::Mel),
+                coin(addr, 2, 7, Denom::Sym),
+            ],
+        );
+        w
+    };
+    let mut mel = shard(Denom::Mel);
+    let sym = shard(Denom::Sym);
+    mel.merge(&sym).unwrap();
+    assert_eq!(mel.balance(Denom::Mel), CoinValue(5000));
+    assert_eq!(mel.balance(Denom::Sym), CoinValue(7));
+    assert_eq!(mel.tracked_denoms, Some([Denom::Mel, Denom::Sym].into()));
+}
+
+#[test]
+fn merge_reports_both_conflicting_transactions() {
+    let s = signer();
+    let mut a = wallet(&s);
+    let addr = a.address;
+    fund(&mut a, vec![coin(addr, 1, 1_000_000, Denom::Mel)]);
+    let mut b = a.clone();
+    // each copy spends the same coin differently
+    let ours = a
+        .prepare_tx(send(vec![burn(1_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    let theirs = b
+        .prepare_tx(send(vec![burn(2_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    a.add_pending(ours.clone()).unwrap();
+    b.add_pending(theirs.clone()).unwrap();
+    let before = a.clone();
+    assert!(matches!(
+        a.merge(&b),
+        Err(MergeError::Conflict { ours: o, theirs: t })
+            if o == ours.hash_nosigs() && t == theirs.hash_nosigs()
+    ));
+    assert_eq!(a, before);
+}
+
+#[test]
+fn rollback_undoes_a_block() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    let (first, _) = coin(addr, 1, 5000, Denom::Mel);
+    fund(&mut w, vec![coin(addr, 1, 5000, Denom::Mel)]);
+    fund(&mut w, vec![coin(addr, 2, 6000, Denom::Mel)]);
+    let before = w.clone();
+    // the third block spends the first coin and creates another
+    w.add_coins(
+        NetID::Testnet,
+        BlockHeight(3),
+        vec![coin(addr, 3, 7000, Denom::Mel)],
+        vec![first],
+    )
+    .unwrap();
+    assert_eq!(w.balances()[&Denom::Mel], CoinValue(13_000));
+
+    w.rollback_to(BlockHeight(2)).unwrap();
+    assert_eq!(w.height, BlockHeight(2));
+    assert_eq!(w.confirmed_utxos, before.confirmed_utxos);
+    assert_eq!(w.balances()[&Denom::Mel], CoinValue(11_000));
+
+    // a different third block replaces it
+    w.add_coins(
+        NetID::Testnet,
+        BlockHeight(3),
+        vec![coin(addr, 4, 8000, Denom::Mel)],
+        vec![],
+    )
+    .unwrap();
+    assert_eq!(w.balances()[&Denom::Mel], CoinValue(19_000));
+    assert!(!w.confirmed_utxos.contains_key(&coin_id(3)));
+}
+
+#[test]
+fn rollback_is_bounded() {
+    let s = signer();
+    let mut w = wallet(&s);
+    for _ in 0..MAX_ROLLBACK_DEPTH + 5 {
+        fund(&mut w, vec![]);
+    }
+    assert!(matches!(
+        w.rollback_to(BlockHeight(1)),
+        Err(RollbackError::TooDeep)
+    ));
+    assert!(matches!(
+        w.rollback_to(w.next_height()),
+        Err(RollbackError::BadHeight)
+    ));
+    w.rollback_to(BlockHeight(5)).unwrap();
+}
+
+#[test]
+fn add_coins_at_jumps_ahead() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    w.add_coins_at(
+        NetID::Testnet,
+        BlockHeight(50),
+        vec![coin(addr, 1, 5000, Denom::Mel)],
+        vec![],
+    )
+    .unwrap();
+    assert_eq!(w.height, BlockHeight(50));
+    assert_eq!(w.balances()[&Denom::Mel], CoinValue(5000));
+    // the strict version still wants the very next block
+    assert!(matches!(
+        w.add_coins(NetID::Testnet, BlockHeight(52), vec![], vec![]),
+        Err(AddCoinsError::BadHeight)
+    ));
+    w.add_coins_at(NetID::Testnet, BlockHeight(52), vec![], vec![])
+        .unwrap();
+}
+
+#[test]
+fn add_coins_at_refuses_to_go_back() {
+    let s = signer();
+    let mut w = wallet(&s);
+    w.add_coins_at(NetID::Testnet, BlockHeight(50), vec![], vec![])
+        .unwrap();
+    for height in [BlockHeight(50), BlockHeight(10)] {
+        assert!(matches!(
+            w.add_coins_at(NetID::Testnet, height, vec![], vec![]),
+            Err(AddCoinsError::BadHeight)
+        ));
+    }
+    assert_eq!(w.height, BlockHeight(50));
+}
+
+#[test]
+fn diffs_of_another_network_are_refused() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(&mut w, vec![coin(addr, 1, 5000, Denom::Mel)]);
+    let before = w.clone();
+    assert!(matches!(
+        w.add_coins(
+            NetID::Mainnet,
+            BlockHeight(2),
+            vec![coin(addr, 2, 5000, Denom::Mel)],
+            vec![],
+        ),
+        Err(AddCoinsError::WrongNetwork)
+    ));
+    assert!(matches!(
+        w.add_coins_range(
+            NetID::Mainnet,
+            vec![(
+                BlockHeight(2),
+                vec![coin(addr, 2, 5000, Denom::Mel)],
+                vec![]
+            )],
+        ),
+        Err(AddCoinsError::WrongNetwork)
+    ));
+    assert!(matches!(
+        w.full_reset(NetID::Mainnet, BlockHeight(10), vec![]),
+        Err(AddCoinsError::WrongNetwork)
+    ));
+    assert_eq!(w, before);
+}
+
+#[test]
+fn untracked_denominations_are_not_stored() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    w.tracked_denoms = Some([Denom::Mel].into());
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 5000, Denom::Mel),
+            coin(addr, 2, 7, Denom::Sym),
+        ],
+    );
+    assert_eq!(w.confirmed_utxos.len(), 1);
+    assert!(w.confirmed_utxos.contains_key(&coin_id(1)));
+    // spends are still honored
+    w.add_coins(NetID::Testnet, BlockHeight(2), vec![], vec![coin_id(1)])
+        .unwrap();
+    assert!(w.confirmed_utxos.is_empty());
+}
+
+#[test]
+fn next_height_follows_the_blocks() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    assert_eq!(w.next_height(), BlockHeight(1));
+    for i in 1..=5 {
+        w.add_coins(
+            NetID::Testnet,
+            w.next_height(),
+            vec![coin(addr, i, 5000, Denom::Mel)],
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(w.next_height(), BlockHeight(i as u64 + 1));
+    }
+    let tx = w
+        .prepare_tx(send(vec![burn(1000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    w.add_pending(tx).unwrap();
+    let status = w.sync_status();
+    assert_eq!(status.local_height, BlockHeight(5));
+    assert_eq!(status.utxo_count, 5);
+    assert_eq!(status.pending_count, 1);
+}
+
+#[test]
+fn add_coins_reports_what_changed() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    let received = w
+        .add_coins(
+            NetID::Testnet,
+            BlockHeight(1),
+            vec![coin(addr, 1, 1_000_000, Denom::Mel)],
+            vec![],
+        )
+        .unwrap();
+    assert_eq!(received.received, vec![coin_id(1)]);
+    assert!(received.confirmed_pending.is_empty());
+
+    let tx = w
+        .prepare_tx(send(vec![burn(10_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    w.add_pending(tx.clone()).unwrap();
+    let change = tx
+        .outputs
+        .iter()
+        .position(|output| output.covhash == addr)
+        .unwrap();
+    let change_id = tx.output_coinid(change as u8);
+    let confirmed = w
+        .add_coins(
+            NetID::Testnet,
+            BlockHeight(2),
+            vec![(change_id, tx.outputs[change].clone())],
+            vec![coin_id(1)],
+        )
+        .unwrap();
+    assert_eq!(confirmed.confirmed_pending, vec![tx.hash_nosigs()]);
+    assert_eq!(confirmed.received, vec![change_id]);
+    assert_eq!(confirmed.spent, vec![coin_id(1)]);
+    // outcomes can be forwarded, for instance to a UI
+    let json = serde_json::to_string(&confirmed).unwrap();
+    assert_eq!(
+        serde_json::from_str::<AddCoinsOutcome>(&json).unwrap(),
+        confirmed
+    );
+}
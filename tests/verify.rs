@@ -0,0 +1,39 @@
+mod common;
+
+use common::*;
+use melstructs::Denom;
+use melwallet::VerifyError;
+
+#[test]
+fn tampered_signatures_fail_verification() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 300_000, Denom::Mel),
+            coin(addr, 2, 400_000, Denom::Mel),
+        ],
+    );
+    let tx = w
+        .prepare_tx(send(vec![burn(500_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    w.verify_signed(&tx).unwrap();
+
+    let mut tampered = tx.clone();
+    let mut sig = tampered.sigs[1].to_vec();
+    sig[10] ^= 1;
+    tampered.sigs[1] = sig.into();
+    assert!(matches!(
+        w.verify_signed(&tampered),
+        Err(VerifyError::Rejected(1))
+    ));
+
+    let mut uncovered = tx;
+    uncovered.covenants.clear();
+    assert!(matches!(
+        w.verify_signed(&uncovered),
+        Err(VerifyError::MissingCovenant(0))
+    ));
+}
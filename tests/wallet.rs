@@ -0,0 +1,60 @@
+mod common;
+
+use common::*;
+use melstructs::{Address, BlockHeight, CoinValue, Denom, NetID};
+use melwallet::{AddCoinsError, Signer, Wallet};
+
+#[test]
+fn wallets_from_signers_accept_their_coins() {
+    let s = signer();
+    let mut w = Wallet::from_signer(NetID::Testnet, &s);
+    assert_eq!(w.height, BlockHeight(0));
+    assert!(w.confirmed_utxos.is_empty());
+    let own = melvm::Covenant::from_bytes(&s.covenant()).unwrap().hash();
+    assert_eq!(w.address, own);
+    w.add_coins(
+        NetID::Testnet,
+        BlockHeight(1),
+        vec![coin(own, 1, 5000, Denom::Mel)],
+        vec![],
+    )
+    .unwrap();
+    assert_eq!(w.balances()[&Denom::Mel], CoinValue(5000));
+
+    let mut other = Wallet::new(NetID::Testnet, Address::coin_destroy());
+    assert!(matches!(
+        other.add_coins(
+            NetID::Testnet,
+            BlockHeight(1),
+            vec![coin(own, 1, 5000, Denom::Mel)],
+            vec![],
+        ),
+        Err(AddCoinsError::WrongAddress)
+    ));
+}
+
+#[test]
+fn watch_only_snapshots_keep_coins_but_not_pending_transactions() {
+    let s = signer();
+    let mut w = wallet(&s);
+    let addr = w.address;
+    fund(
+        &mut w,
+        vec![
+            coin(addr, 1, 1_000_000, Denom::Mel),
+            coin(addr, 2, 5, Denom::Sym),
+        ],
+    );
+    let tx = w
+        .prepare_tx(send(vec![burn(10_000, Denom::Mel)]), &s, FEE_MULTIPLIER)
+        .unwrap();
+    w.add_pending(tx).unwrap();
+
+    let snapshot = w.watch_only_snapshot();
+    assert_eq!(snapshot.balances(), w.balances());
+    assert_eq!(snapshot.height, w.height);
+    assert!(snapshot.pending_outgoing.is_empty());
+    assert!(snapshot.pending_heights.is_empty());
+    assert_eq!(snapshot.available_balances(), w.balances());
+    assert!(!w.pending_outgoing.is_empty());
+}